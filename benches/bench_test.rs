@@ -1,28 +1,26 @@
-#![feature(test)]
-
-extern crate test;
-
-use test::{Bencher};
+use std::time::Instant;
 use rustcachedb::{CacheDb, CacheClient, CacheDbError, KeyValObj};
 
-#[derive(Clone, Default, Debug, PartialEq)]
+const BENCH_ITERATIONS: u32 = 10_000;
+
+#[derive(Clone, Default, Debug, PartialEq, Eq, Hash)]
 struct CacheString(String);
 
 impl rustcachedb::GenericKeyVal<CacheString> for CacheString {
-    fn get_size(self: &Self) -> Result<u16, CacheDbError> {
+    fn get_size(&self) -> Result<u32, CacheDbError> {
         match self.0.chars().count().try_into() {
             Ok(size) => {
-                return Ok(size);
+                Ok(size)
             }
             Err(_) => {
-                return Err(CacheDbError::ProtocolSizeBufferOverflow);
+                Err(CacheDbError::ProtocolSizeBufferOverflow)
             }
         }
     }
 
     // must clone string since into_bytes() is not implemented for
     // the string reference (at least not the copy trait which is required)
-    fn get_bytes(self: &Self) -> Vec<u8> {
+    fn get_bytes(&self) -> Vec<u8> {
         let str_bytes = self.clone();
         str_bytes.0.into_bytes()
     }
@@ -35,19 +33,25 @@ impl rustcachedb::GenericKeyVal<CacheString> for CacheString {
     }
 }
 
-#[bench]
-fn basic_client_pull_bench(b: &mut Bencher) {
-    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 8080);
-    CacheDb::<CacheString, CacheString>::cache_db_server(&cache);
+fn basic_client_pull_bench() {
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 0);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
     cache.push(KeyValObj{key: CacheString("asd".to_string()), val: CacheString("das".to_string())});
     
-    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], 8080).unwrap();
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], cache_db_server.local_addr().unwrap().port()).unwrap();
     CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
     // .join().unwrap();
 
     let mut _test_res = KeyValObj{key: CacheString(String::new()), val: CacheString(String::new())};
     let pull_key = CacheString("asd".to_string());
-    b.iter(|| {
+    let start = Instant::now();
+    for _ in 0..BENCH_ITERATIONS {
         cache_client.pull(&pull_key, &mut _test_res).unwrap();   
-    });
+    }
+    println!("basic_client_pull_bench: {:?}/iter", start.elapsed() / BENCH_ITERATIONS);
+    cache_db_server.shutdown().unwrap();
+}
+
+fn main() {
+    basic_client_pull_bench();
 }
@@ -0,0 +1,65 @@
+use std::time::Instant;
+use rustcachedb::{CacheDb, CacheClient, CacheDbError, KeyValObj};
+
+const BENCH_ITERATIONS: u32 = 10_000;
+const KEY_COUNTS: [u32; 4] = [1_000, 10_000, 100_000, 1_000_000];
+
+#[derive(Clone, Default, Debug, PartialEq, Eq, Hash)]
+struct CacheString(String);
+
+impl rustcachedb::GenericKeyVal<CacheString> for CacheString {
+    fn get_size(&self) -> Result<u32, CacheDbError> {
+        match self.0.chars().count().try_into() {
+            Ok(size) => {
+                Ok(size)
+            }
+            Err(_) => {
+                Err(CacheDbError::ProtocolSizeBufferOverflow)
+            }
+        }
+    }
+
+    fn get_bytes(&self) -> Vec<u8> {
+        let str_bytes = self.clone();
+        str_bytes.0.into_bytes()
+    }
+
+    fn from_bytes(data: &[u8]) -> Result<Self, CacheDbError> {
+        if let Ok(data_str) = std::str::from_utf8(data) {
+            return Ok(Self(data_str.to_string()));
+        }
+        Err(CacheDbError::DecodingErr)
+    }
+}
+
+// pull latency should stay flat with a growing key count since the store is hash indexed
+fn key_count_pull_bench(key_count: u32) {
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 0);
+    for i in 0..key_count {
+        cache.push(KeyValObj{key: CacheString(format!("key{}", i)), val: CacheString(format!("val{}", i))});
+    }
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], cache_db_server.local_addr().unwrap().port()).unwrap();
+    CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+
+    let mut res = KeyValObj{key: CacheString(String::new()), val: CacheString(String::new())};
+    let start = Instant::now();
+    for i in 0..BENCH_ITERATIONS {
+        cache_client.pull(&CacheString(format!("key{}", i % key_count)), &mut res).unwrap();
+    }
+    println!("key_count_pull_bench {:>9} keys: {:?}/pull", key_count, start.elapsed() / BENCH_ITERATIONS);
+
+    let start = Instant::now();
+    for i in 0..BENCH_ITERATIONS {
+        cache.get(&CacheString(format!("key{}", i % key_count))).unwrap();
+    }
+    println!("key_count_get_bench  {:>9} keys: {:?}/get", key_count, start.elapsed() / BENCH_ITERATIONS);
+    cache_db_server.shutdown().unwrap();
+}
+
+fn main() {
+    for key_count in KEY_COUNTS.iter() {
+        key_count_pull_bench(*key_count);
+    }
+}
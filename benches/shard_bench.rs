@@ -0,0 +1,77 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+use rustcachedb::{CacheDb, CacheDbConfig, CacheDbError, KeyValObj};
+
+const OPS_PER_THREAD: u32 = 200_000;
+const KEYS_PER_THREAD: u32 = 10_000;
+
+#[derive(Clone, Default, Debug, PartialEq, Eq, Hash)]
+struct CacheString(String);
+
+impl rustcachedb::GenericKeyVal<CacheString> for CacheString {
+    fn get_size(&self) -> Result<u32, CacheDbError> {
+        match self.0.chars().count().try_into() {
+            Ok(size) => {
+                Ok(size)
+            }
+            Err(_) => {
+                Err(CacheDbError::ProtocolSizeBufferOverflow)
+            }
+        }
+    }
+
+    fn get_bytes(&self) -> Vec<u8> {
+        let str_bytes = self.clone();
+        str_bytes.0.into_bytes()
+    }
+
+    fn from_bytes(data: &[u8]) -> Result<Self, CacheDbError> {
+        if let Ok(data_str) = std::str::from_utf8(data) {
+            return Ok(Self(data_str.to_string()));
+        }
+        Err(CacheDbError::DecodingErr)
+    }
+}
+
+// write heavy workload (3 pushes per get), throughput should scale with the thread count if the store is sharded
+// the bounded cache only holds half of the keys, so that the pushes keep evicting
+fn shard_throughput_bench(shard_count: usize, thread_count: u32, bounded: bool) {
+    let max_entries = bounded.then_some((KEYS_PER_THREAD * thread_count / 2) as usize);
+    let config = CacheDbConfig{shard_count, max_entries, ..Default::default()};
+    let cache = CacheDb::<CacheString, CacheString>::new_with_config([127, 0, 0, 1], 0, config);
+
+    let start = Instant::now();
+    let workers: Vec<_> = (0..thread_count).map(|t| {
+        let cache = Arc::clone(&cache);
+        thread::spawn(move || {
+            for i in 0..OPS_PER_THREAD {
+                let key = CacheString(format!("key{}_{}", t, i % KEYS_PER_THREAD));
+                if i % 4 == 0 {
+                    cache.get(&key);
+                } else {
+                    cache.push(KeyValObj{key, val: CacheString(format!("val{}", i))});
+                }
+            }
+        })
+    }).collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    let ops = f64::from(OPS_PER_THREAD * thread_count);
+    let setup = if bounded { "bounded" } else { "unbounded" };
+    println!("shard_throughput_bench {:>9} {:>2} shards {:>2} threads: {:>10.0} ops/s", setup, shard_count, thread_count, ops / start.elapsed().as_secs_f64());
+}
+
+fn main() {
+    let max_threads = thread::available_parallelism().map(|n| n.get() as u32).unwrap_or(4);
+    for bounded in [false, true] {
+        for shard_count in [1, 16] {
+            let mut thread_count = 1;
+            while thread_count <= max_threads {
+                shard_throughput_bench(shard_count, thread_count, bounded);
+                thread_count *= 2;
+            }
+        }
+    }
+}
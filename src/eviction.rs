@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// picks the keys that are evicted once a bounded CacheDb reached its limit
+// the policy is informed about every change of the key_val_store and keeps its own record of the keys
+pub trait EvictionPolicy<KeyT>: Send {
+    // key has been added to the store
+    fn on_insert(&mut self, key: &KeyT);
+    // existing key has been read or updated
+    fn on_access(&mut self, key: &KeyT);
+    // key has been removed from the store (not called for keys returned by victim)
+    fn on_remove(&mut self, key: &KeyT);
+    // returns the next key to evict and forgets about it
+    fn victim(&mut self) -> Option<KeyT>;
+}
+
+// evicts the least recently used key
+pub struct LruPolicy<KeyT> {
+    tick: u64,
+    key_ticks: HashMap<KeyT, u64>,
+    // ordered by last access
+    tick_keys: BTreeMap<u64, KeyT>,
+}
+
+impl<KeyT: Hash + Eq + Clone> LruPolicy<KeyT> {
+    pub fn new() -> LruPolicy<KeyT> {
+        LruPolicy { tick: 0, key_ticks: HashMap::new(), tick_keys: BTreeMap::new() }
+    }
+}
+
+impl<KeyT: Hash + Eq + Clone> Default for LruPolicy<KeyT> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<KeyT: Hash + Eq + Clone + Send> EvictionPolicy<KeyT> for LruPolicy<KeyT> {
+    fn on_insert(&mut self, key: &KeyT) {
+        self.on_access(key);
+    }
+
+    fn on_access(&mut self, key: &KeyT) {
+        self.tick += 1;
+        if let Some(old_tick) = self.key_ticks.insert(key.clone(), self.tick) {
+            self.tick_keys.remove(&old_tick);
+        }
+        self.tick_keys.insert(self.tick, key.clone());
+    }
+
+    fn on_remove(&mut self, key: &KeyT) {
+        if let Some(tick) = self.key_ticks.remove(key) {
+            self.tick_keys.remove(&tick);
+        }
+    }
+
+    fn victim(&mut self) -> Option<KeyT> {
+        let (_, key) = self.tick_keys.pop_first()?;
+        self.key_ticks.remove(&key);
+        Some(key)
+    }
+}
+
+// evicts the least frequently used key, the least recently used one if there are multiple
+pub struct LfuPolicy<KeyT> {
+    tick: u64,
+    // access count and last access
+    key_uses: HashMap<KeyT, (u64, u64)>,
+    // ordered by access count and then by last access
+    use_keys: BTreeMap<(u64, u64), KeyT>,
+}
+
+impl<KeyT: Hash + Eq + Clone> LfuPolicy<KeyT> {
+    pub fn new() -> LfuPolicy<KeyT> {
+        LfuPolicy { tick: 0, key_uses: HashMap::new(), use_keys: BTreeMap::new() }
+    }
+}
+
+impl<KeyT: Hash + Eq + Clone> Default for LfuPolicy<KeyT> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<KeyT: Hash + Eq + Clone + Send> EvictionPolicy<KeyT> for LfuPolicy<KeyT> {
+    fn on_insert(&mut self, key: &KeyT) {
+        self.on_access(key);
+    }
+
+    fn on_access(&mut self, key: &KeyT) {
+        self.tick += 1;
+        let mut count = 1;
+        if let Some(old_use) = self.key_uses.get(key) {
+            count += old_use.0;
+            self.use_keys.remove(old_use);
+        }
+        self.key_uses.insert(key.clone(), (count, self.tick));
+        self.use_keys.insert((count, self.tick), key.clone());
+    }
+
+    fn on_remove(&mut self, key: &KeyT) {
+        if let Some(old_use) = self.key_uses.remove(key) {
+            self.use_keys.remove(&old_use);
+        }
+    }
+
+    fn victim(&mut self) -> Option<KeyT> {
+        let (_, key) = self.use_keys.pop_first()?;
+        self.key_uses.remove(&key);
+        Some(key)
+    }
+}
+
+// evicts a random key
+pub struct RandomPolicy<KeyT> {
+    // xorshift state
+    rand_state: u64,
+    keys: Vec<KeyT>,
+    key_indices: HashMap<KeyT, usize>,
+}
+
+impl<KeyT: Hash + Eq + Clone> RandomPolicy<KeyT> {
+    pub fn new() -> RandomPolicy<KeyT> {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|t| t.as_nanos() as u64).unwrap_or(0);
+        RandomPolicy::with_seed(seed)
+    }
+
+    pub fn with_seed(seed: u64) -> RandomPolicy<KeyT> {
+        // xorshift must not be seeded with 0
+        RandomPolicy { rand_state: seed | 1, keys: Vec::new(), key_indices: HashMap::new() }
+    }
+
+    fn next_rand(&mut self) -> u64 {
+        self.rand_state ^= self.rand_state << 13;
+        self.rand_state ^= self.rand_state >> 7;
+        self.rand_state ^= self.rand_state << 17;
+        self.rand_state
+    }
+
+    fn remove_at(&mut self, i: usize) -> KeyT {
+        let key = self.keys.swap_remove(i);
+        self.key_indices.remove(&key);
+        if let Some(moved_key) = self.keys.get(i) {
+            self.key_indices.insert(moved_key.clone(), i);
+        }
+        key
+    }
+}
+
+impl<KeyT: Hash + Eq + Clone> Default for RandomPolicy<KeyT> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<KeyT: Hash + Eq + Clone + Send> EvictionPolicy<KeyT> for RandomPolicy<KeyT> {
+    fn on_insert(&mut self, key: &KeyT) {
+        if !self.key_indices.contains_key(key) {
+            self.key_indices.insert(key.clone(), self.keys.len());
+            self.keys.push(key.clone());
+        }
+    }
+
+    fn on_access(&mut self, _key: &KeyT) {}
+
+    fn on_remove(&mut self, key: &KeyT) {
+        if let Some(i) = self.key_indices.get(key).copied() {
+            self.remove_at(i);
+        }
+    }
+
+    fn victim(&mut self) -> Option<KeyT> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        let i = (self.next_rand() % self.keys.len() as u64) as usize;
+        Some(self.remove_at(i))
+    }
+}
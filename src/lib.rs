@@ -2,27 +2,108 @@ use std::fmt::Debug;
 use std::io;
 use std::io::prelude::*;
 use std::marker::PhantomData;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Wake, Waker};
 use std::thread;
+use std::hash::Hash;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{BuildHasher, RandomState};
 use std::thread::JoinHandle;
 use std::cmp::PartialEq;
-use std::time::Duration;
-use std::net::{SocketAddr, TcpListener, TcpStream};
-use std::sync::{Arc, RwLock, Mutex, Condvar};
+use std::time::{Duration, Instant};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{mpsc, Arc, Weak, RwLock, Mutex, Condvar};
 use std::marker::{Send, Sync};
-
-const TCP_READ_BUFF_SIZE: usize = 1024;
-const CACHE_CLIENT_REQ_SIG_WAIT: Duration = Duration::from_secs(10);
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+pub mod eviction;
+pub use eviction::{EvictionPolicy, LruPolicy, LfuPolicy, RandomPolicy};
+
+const TCP_READ_BUFF_SIZE: usize = 16 * 1024;
+const CACHE_PROTOCOL_MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+const CACHE_CLIENT_DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const CACHE_DB_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+// max number of expired keys removed per write lock of the key_val_store
+const CACHE_DB_SWEEP_BATCH_SIZE: usize = 64;
+const CACHE_DB_DEFAULT_SHARD_COUNT: usize = 16;
+const CACHE_DB_DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const CACHE_DB_DEFAULT_WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+// batches of InvalidateOps and ChangeNotifyOps per connection that have not been written yet
+const CACHE_DB_OUTBOUND_QUEUE_SIZE: usize = 1024;
+const CACHE_DB_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+const CACHE_DB_SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// version of the frame layout, client and server must use the same version
+pub const CACHE_PROTOCOL_VERSION: u16 = 1;
+// capability bits of the hello frame
+pub const CACHE_PROTOCOL_CAP_LARGE_FRAMES: u32 = 1;
+pub const CACHE_PROTOCOL_CAP_TTL: u32 = 1 << 1;
+// reserved, not supported yet
+pub const CACHE_PROTOCOL_CAP_COMPRESSION: u32 = 1 << 2;
+// the server sends an InvalidateOp once a key the client pulled changed, only requested by clients with a near cache
+pub const CACHE_PROTOCOL_CAP_INVALIDATION: u32 = 1 << 3;
 
 // todo => remove potentially unnecessary iterations over the key_val_stores (benchmarks)
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum CacheDbError {
     KeyNotFound,
     ParsingErr,
     DecodingErr,
     ProtocolSizeBufferOverflow,
     NetworkError,
-    NetworkTimeOutError
+    NetworkTimeOutError,
+    // client and server use different protocol versions
+    ProtocolVersionMismatch,
+    // client and server disagree on the frame mode or the server lacks a capability required by the client
+    CapabilityMismatch,
+    // error reply of the server with a code the client does not know
+    ServerError { code: u16, message: String },
+    // the server acknowledged the push but did not store it because of the push mode
+    NotStored,
+}
+
+// error codes of the ErrorReplyOp
+const CACHE_PROTOCOL_ERR_PARSING: u16 = 1;
+const CACHE_PROTOCOL_ERR_DECODING: u16 = 2;
+const CACHE_PROTOCOL_ERR_SIZE_OVERFLOW: u16 = 3;
+const CACHE_PROTOCOL_ERR_UNKNOWN: u16 = 0xffff;
+
+impl CacheDbError {
+    fn error_code(&self) -> u16 {
+        match self {
+            CacheDbError::ParsingErr => CACHE_PROTOCOL_ERR_PARSING,
+            CacheDbError::DecodingErr => CACHE_PROTOCOL_ERR_DECODING,
+            CacheDbError::ProtocolSizeBufferOverflow => CACHE_PROTOCOL_ERR_SIZE_OVERFLOW,
+            CacheDbError::ServerError{code, ..} => *code,
+            _ => CACHE_PROTOCOL_ERR_UNKNOWN,
+        }
+    }
+
+    fn from_io_error(e: &io::Error) -> CacheDbError {
+        match e.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => CacheDbError::NetworkTimeOutError,
+            _ => CacheDbError::NetworkError,
+        }
+    }
+
+    // the message is only kept for codes that have no variant of their own
+    fn from_error_code(code: u16, message: String) -> CacheDbError {
+        match code {
+            CACHE_PROTOCOL_ERR_PARSING => CacheDbError::ParsingErr,
+            CACHE_PROTOCOL_ERR_DECODING => CacheDbError::DecodingErr,
+            CACHE_PROTOCOL_ERR_SIZE_OVERFLOW => CacheDbError::ProtocolSizeBufferOverflow,
+            _ => CacheDbError::ServerError{code, message},
+        }
+    }
 }
 
 #[derive(PartialEq, Clone, Copy, Debug)]
@@ -32,6 +113,53 @@ pub enum ProtOpCode {
     PullReplyOp = 3,
     PullReplyNotFoundOp = 4,
     TerminateConn = 5,
+    PushInsertOp = 6,
+    PushUpdateOp = 7,
+    DeleteOp = 8,
+    DeleteReplyOp = 9,
+    DeleteReplyNotFoundOp = 10,
+    TtlOp = 11,
+    TtlReplyOp = 12,
+    TtlReplyNotFoundOp = 13,
+    HelloOp = 14,
+    HelloReplyOp = 15,
+    ErrorReplyOp = 16,
+    PushAckOp = 17,
+    PushAckNotStoredOp = 18,
+    MultiPullOp = 19,
+    MultiPullReplyOp = 20,
+    MultiPushOp = 21,
+    // sent by the server without request id
+    InvalidateOp = 22,
+    WatchOp = 23,
+    WatchPrefixOp = 24,
+    UnwatchOp = 25,
+    UnwatchPrefixOp = 26,
+    WatchAckOp = 27,
+    // sent by the server without request id
+    ChangeNotifyOp = 28,
+    ChangeNotifyDeletedOp = 29,
+}
+
+// decides how a push treats an already existing key (similar to the redis NX/ XX flags)
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum PushMode {
+    // inserts the key or replaces the value of an existing key
+    Upsert,
+    // only inserts the key if it does not exist yet
+    InsertOnly,
+    // only replaces the value if the key already exists
+    UpdateOnly,
+}
+
+impl PushMode {
+    fn op_code(&self) -> ProtOpCode {
+        match self {
+            PushMode::Upsert => ProtOpCode::PushOp,
+            PushMode::InsertOnly => ProtOpCode::PushInsertOp,
+            PushMode::UpdateOnly => ProtOpCode::PushUpdateOp,
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -40,530 +168,3311 @@ pub struct KeyValObj<KeyT, ValT> {
     pub val: ValT,
 }
 
-// 0 contains the key_val pair, 1 indicates wether the key could be found 
-pub struct KeyValObjSyncLocked<KeyT, ValT>(KeyValObj<KeyT, ValT>, bool);
-pub struct KeyValObjSync<KeyT, ValT> {
-    // only true if data is currently requested (pulled)
-    pub pulling: Mutex<bool>,
-    pub pulling_sig: Condvar,
+// stored obj and the point in time it expires at (if any)
+struct CacheEntry<KeyT, ValT> {
+    obj: KeyValObj<KeyT, ValT>,
+    expires_at: Option<Instant>,
+    // key and val size in bytes
+    size: usize,
+}
 
-    pub key_val: RwLock<KeyValObjSyncLocked<KeyT, ValT>>
+impl<KeyT, ValT> CacheEntry<KeyT, ValT> {
+    fn is_expired(&self, now: Instant) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= now)
+    }
 }
 
-pub struct CacheDb<KeyT, ValT> {
-    ipv4_addr: [u8; 4],
-    port: u16,
+// number of entries and their total size in bytes over all shards
+#[derive(Default)]
+struct StoreUsage {
+    entries: AtomicUsize,
+    bytes: AtomicUsize,
+}
 
-    key_val_store: RwLock<Vec<Box<KeyValObj<KeyT, ValT>>>>
+// entries of a shard, every change is added to the usage of the CacheDb
+struct KeyValStore<KeyT, ValT> {
+    entries: HashMap<KeyT, CacheEntry<KeyT, ValT>>,
+    usage: Arc<StoreUsage>,
 }
 
-pub struct CacheProtocol<KeyT, ValT> {
-    // points to the segment currently read
-    parsed_protocoll_segment: u8,
-    parsed_bytes_total: usize,
-    to_parse_bytes_total: usize,
-    key_size: u16,
-    val_size: u16,
+impl<KeyT: Hash + Eq, ValT> KeyValStore<KeyT, ValT> {
+    fn new(usage: Arc<StoreUsage>) -> KeyValStore<KeyT, ValT> {
+        KeyValStore { entries: HashMap::new(), usage }
+    }
 
-    // because of unconstrained type conflict
-    pd_k: PhantomData<KeyT>,
-    pd_v: PhantomData<ValT>,
+    fn insert(&mut self, key: KeyT, entry: CacheEntry<KeyT, ValT>) -> Option<CacheEntry<KeyT, ValT>> {
+        self.usage.bytes.fetch_add(entry.size, Ordering::Relaxed);
+        let replaced = self.entries.insert(key, entry);
+        match &replaced {
+            Some(replaced) => {
+                self.usage.bytes.fetch_sub(replaced.size, Ordering::Relaxed);
+            }
+            None => {
+                self.usage.entries.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        replaced
+    }
+
+    fn remove(&mut self, key: &KeyT) -> Option<CacheEntry<KeyT, ValT>> {
+        let removed = self.entries.remove(key)?;
+        self.usage.entries.fetch_sub(1, Ordering::Relaxed);
+        self.usage.bytes.fetch_sub(removed.size, Ordering::Relaxed);
+        Some(removed)
+    }
 }
 
-pub struct CacheClient<KeyT, ValT> {
-    key_val_sync_store: RwLock<Vec<KeyValObjSync<KeyT, ValT>>>,
-    tcp_conn: RwLock<TcpStream>,
+// independently locked part of the CacheDb, keys are assigned to the shards by their hash
+struct Shard<KeyT, ValT> {
+    key_val_store: RwLock<KeyValStore<KeyT, ValT>>,
+    // only informed about changes if there is a limit, picks the victims among the keys of the shard
+    eviction_policy: Mutex<Box<dyn EvictionPolicy<KeyT>>>,
+}
 
-    // because of unconstrained type conflict
-    pd_k: PhantomData<KeyT>,
-    pd_v: PhantomData<ValT>,
+// limits of a CacheDb, if one is reached the eviction_policy picks the keys to remove
+// the limits apply to the whole CacheDb, not to the single shards
+pub struct CacheDbConfig<KeyT> {
+    // max number of keys, None for no limit
+    pub max_entries: Option<usize>,
+    // max sum of all key and val sizes (GenericKeyVal::get_size), None for no limit
+    pub max_bytes: Option<usize>,
+    // creates the eviction policy of each shard
+    pub eviction_policy: fn() -> Box<dyn EvictionPolicy<KeyT>>,
+    // number of independently locked parts of the key_val_store
+    pub shard_count: usize,
+    // frame mode the clients have to use
+    pub frame_mode: FrameMode,
+    pub max_frame_size: usize,
+    // sent to the clients in the hello reply
+    pub server_identity: String,
+    // connections that did not send their hello within the timeout are closed
+    pub handshake_timeout: Duration,
+    pub server_event_hook: Option<ServerEventHook>,
+    // the server additionally listens on the unix socket, an existing socket file that is not in use is replaced
+    pub unix_socket_path: Option<PathBuf>,
+    // connections that can not be written to within the timeout are closed, None waits as long as the os does
+    pub write_timeout: Option<Duration>,
 }
 
-pub trait GenericKeyVal<Val> {
-    fn get_size(&self) -> Result<u16, CacheDbError>;
-    fn get_bytes(&self) -> Vec<u8>;
-    fn from_bytes(data: &[u8]) -> Result<Val, CacheDbError>;
+impl<KeyT: Hash + Eq + Clone + Send + 'static> Default for CacheDbConfig<KeyT> {
+    fn default() -> Self {
+        CacheDbConfig {
+            max_entries: None,
+            max_bytes: None,
+            eviction_policy: || Box::new(LruPolicy::new()),
+            shard_count: CACHE_DB_DEFAULT_SHARD_COUNT,
+            frame_mode: FrameMode::Standard,
+            max_frame_size: CACHE_PROTOCOL_MAX_FRAME_SIZE,
+            server_identity: format!("rustcachedb/{}", env!("CARGO_PKG_VERSION")),
+            handshake_timeout: CACHE_DB_DEFAULT_HANDSHAKE_TIMEOUT,
+            server_event_hook: None,
+            unix_socket_path: None,
+            write_timeout: Some(CACHE_DB_DEFAULT_WRITE_TIMEOUT),
+        }
+    }
 }
 
-impl<KeyT, ValT> CacheProtocol<KeyT, ValT> where KeyT: GenericKeyVal<KeyT>, ValT: GenericKeyVal<ValT> + Debug {
-    fn prot_op_code_to_u8_be(op_code: &ProtOpCode) -> u8 {
-        match op_code {
-            ProtOpCode::PullOp => u8::from_le(1),
-            ProtOpCode::PushOp => u8::from_le(2),
-            ProtOpCode::PullReplyOp => u8::from_le(3),
-            ProtOpCode::PullReplyNotFoundOp => u8::from_le(4),
-            ProtOpCode::TerminateConn => u8::from_le(5),
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub struct CacheDbStats {
+    pub entries: usize,
+    pub bytes: usize,
+    // total number of keys evicted because of the CacheDbConfig limits
+    pub evictions: u64,
+}
+
+// reply of a request made by the CacheClient, set by the cache_client_handler
+struct ReplySync<T> {
+    reply: Mutex<Option<T>>,
+    reply_sig: Condvar,
+    // CacheFutures that await the reply, every future keeps the index of its waker
+    reply_wakers: Mutex<Vec<Waker>>,
+}
+
+impl<T> ReplySync<T> {
+    fn new() -> ReplySync<T> {
+        ReplySync { reply: Mutex::new(None), reply_sig: Condvar::new(), reply_wakers: Mutex::new(Vec::new()) }
+    }
+
+    fn set_reply(&self, reply: T) {
+        let mut reply_lock = self.reply.lock().unwrap();
+        *reply_lock = Some(reply);
+        let reply_wakers = std::mem::take(&mut *self.reply_wakers.lock().unwrap());
+        drop(reply_lock);
+        self.reply_sig.notify_all();
+        for reply_waker in reply_wakers {
+            reply_waker.wake();
         }
     }
-    fn u8_to_prot_op_code_le(op_code: u8) -> Option<ProtOpCode> {
-        match u8::from_be(op_code) {
-            1 => Some(ProtOpCode::PullOp),
-            2 => Some(ProtOpCode::PushOp),
-            3 => Some(ProtOpCode::PullReplyOp),
-            4 => Some(ProtOpCode::PullReplyNotFoundOp),
-            5 => Some(ProtOpCode::TerminateConn),
-            _ => None,
+
+    // stores the waker of the future, its previous waker is only replaced if it would not wake the same task
+    // the wakers are only taken by set_reply, so the index stays valid until the reply has been set
+    fn register_waker(&self, cx: &mut Context<'_>, waker_index: &mut Option<usize>) {
+        let mut reply_wakers = self.reply_wakers.lock().unwrap();
+        match *waker_index {
+            Some(i) => {
+                if !reply_wakers[i].will_wake(cx.waker()) {
+                    reply_wakers[i].clone_from(cx.waker());
+                }
+            }
+            None => {
+                *waker_index = Some(reply_wakers.len());
+                reply_wakers.push(cx.waker().clone());
+            }
         }
     }
 
-    pub fn assemble_buff(op_code: ProtOpCode, obj: &KeyValObj<KeyT, ValT>) -> Result<Vec<u8>, CacheDbError> {
-        let mut buff = Vec::<u8>::new();
-        buff.push(CacheProtocol::<KeyT, ValT>::prot_op_code_to_u8_be(&op_code));
-        let key_size = obj.key.get_size()?;
-        buff.extend_from_slice(&u16::from_le(key_size).to_be_bytes());
-        let mut key_bytes = obj.key.get_bytes();
-        buff.append(&mut key_bytes);
+    // the waker is stored while the reply is locked, so that it can not miss the set_reply
+    fn poll_reply(&self, cx: &mut Context<'_>, waker_index: &mut Option<usize>) -> Poll<T> {
+        let mut reply_lock = self.reply.lock().unwrap();
+        match reply_lock.take() {
+            Some(reply) => Poll::Ready(reply),
+            None => {
+                self.register_waker(cx, waker_index);
+                Poll::Pending
+            }
+        }
+    }
 
-        if op_code != ProtOpCode::PullOp {
-            buff.extend_from_slice(&u16::from_le(obj.val.get_size()?).to_be_bytes());
-        } else {
-            buff.extend_from_slice(&[0_u8, 0_u8]);
+    // unlike poll_reply the reply is kept for the other futures
+    fn poll_shared_reply(&self, cx: &mut Context<'_>, waker_index: &mut Option<usize>) -> Poll<T> where T: Clone {
+        let reply_lock = self.reply.lock().unwrap();
+        match reply_lock.as_ref() {
+            Some(reply) => Poll::Ready(reply.clone()),
+            None => {
+                self.register_waker(cx, waker_index);
+                Poll::Pending
+            }
         }
-        if op_code != ProtOpCode::PullOp {
-            let mut val_bytes = obj.val.get_bytes();
-            buff.append(&mut val_bytes);
+    }
+
+    fn wait_reply(&self, timeout: Duration) -> Result<T, CacheDbError> {
+        let reply_lock = self.reply.lock().unwrap();
+        let (mut reply_lock, wait_res) = self.reply_sig.wait_timeout_while(reply_lock, timeout, |reply| reply.is_none()).unwrap();
+        if wait_res.timed_out() {
+            return Err(CacheDbError::NetworkTimeOutError);
         }
+        reply_lock.take().ok_or(CacheDbError::NetworkError)
+    }
 
-        Ok(buff)
+    // unlike wait_reply the reply is kept for the other waiters
+    fn wait_shared_reply(&self, timeout: Duration) -> Result<T, CacheDbError> where T: Clone {
+        let reply_lock = self.reply.lock().unwrap();
+        let (reply_lock, wait_res) = self.reply_sig.wait_timeout_while(reply_lock, timeout, |reply| reply.is_none()).unwrap();
+        if wait_res.timed_out() {
+            return Err(CacheDbError::NetworkTimeOutError);
+        }
+        reply_lock.clone().ok_or(CacheDbError::NetworkError)
     }
+}
 
-    pub fn new() -> CacheProtocol<KeyT, ValT> {
-        CacheProtocol {
-            parsed_protocoll_segment: 0,
-            parsed_bytes_total: 0,
-            to_parse_bytes_total: 0,
-            key_size: 0,
-            val_size: 0,
-            pd_k: PhantomData,
-            pd_v: PhantomData,
+type PollFn<T> = dyn FnMut(&mut Context<'_>) -> Poll<Result<T, CacheDbError>> + Send;
+
+// returned by the async methods of the CacheClient, resolved by the cache_client_handler once the reply arrived
+// the request is sent when the future is created, so it does not have to be polled for the request to be made
+pub struct CacheFuture<T> {
+    poll_fn: Box<PollFn<T>>,
+}
+
+impl<T: Send + 'static> CacheFuture<T> {
+    fn ready(res: Result<T, CacheDbError>) -> CacheFuture<T> {
+        let mut res = Some(res);
+        CacheFuture { poll_fn: Box::new(move |_| Poll::Ready(res.take().unwrap_or(Err(CacheDbError::NetworkError)))) }
+    }
+}
+
+impl<T> Future for CacheFuture<T> {
+    type Output = Result<T, CacheDbError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        (self.poll_fn)(cx)
+    }
+}
+
+struct ThreadWaker(thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// minimal executor for sync callers, blocks the current thread until the future is resolved
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            // spurious unparks only lead to another poll
+            Poll::Pending => thread::park(),
         }
     }
+}
 
-    // things to notice: tcp data can come in at different sizes(only order is guaranteed - FIFO)
-    // so this parsing method tries to account for that by keeping states
-    pub fn parse_buff(&mut self, buff: &mut [u8; TCP_READ_BUFF_SIZE], tcp_read_size: usize, op_code: &mut ProtOpCode, obj: &mut KeyValObj<KeyT, ValT>) -> Result<(bool, usize), CacheDbError> {
-        let mut key_valsize_raw: [u8; 2] = [0; 2];
-        let mut op_code_raw: u8;
+// like block_on, but gives up once the deadline passed and returns None
+pub fn block_on_with_deadline<F: Future>(future: F, deadline: Instant) -> Option<F::Output> {
+    let mut future = std::pin::pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return Some(output);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
+        }
+        thread::park_timeout(deadline - now);
+    }
+}
 
-        loop {
-            match self.parsed_protocoll_segment {
-                // parsing protocol op_code
-                0 if { tcp_read_size >= self.parsed_bytes_total+1 } => {
-                    self.to_parse_bytes_total +=1;
-                    op_code_raw = buff[self.parsed_bytes_total];
-
-                    if let Some(op_code_en) = &CacheProtocol::<KeyT, ValT>::u8_to_prot_op_code_le(op_code_raw) {
-                        op_code.clone_from(op_code_en);
-                    } else {
-                        return Err(CacheDbError::ParsingErr);
-                    }
-                    self.parsed_protocoll_segment += 1;
+// reply frame to a request
+struct ReplyFrame<KeyT, ValT> {
+    op_code: ProtOpCode,
+    ttl: Option<Duration>,
+    // set for error replies
+    error: Option<CacheDbError>,
+    // set for multi replies
+    entries: Vec<(KeyValObj<KeyT, ValT>, bool)>,
+}
 
-                    self.parsed_bytes_total = self.to_parse_bytes_total;
-                    self.to_parse_bytes_total += 2;
-                }
-                // parsing protocol key size
-                1 if { tcp_read_size >= self.to_parse_bytes_total } => {
-                    key_valsize_raw.copy_from_slice(&buff[self.parsed_bytes_total..self.to_parse_bytes_total]);
-                    self.key_size = u16::from_be_bytes(key_valsize_raw);
-                    self.parsed_protocoll_segment += 1;
+impl<KeyT, ValT> ReplyFrame<KeyT, ValT> {
+    fn from_error(error: CacheDbError) -> ReplyFrame<KeyT, ValT> {
+        ReplyFrame{op_code: ProtOpCode::ErrorReplyOp, ttl: None, error: Some(error), entries: Vec::new()}
+    }
+}
 
-                    self.parsed_bytes_total = self.to_parse_bytes_total;
-                    self.to_parse_bytes_total += usize::from(self.key_size);
-                }
-                // parsing protocol key
-                2 if { tcp_read_size >= self.to_parse_bytes_total } => {
-                    if self.key_size != 0 {
-                        obj.key = KeyT::from_bytes(&buff[self.parsed_bytes_total..self.to_parse_bytes_total])?;
-                    }
-                    self.parsed_protocoll_segment += 1;
+// result of a pull, shared by all callers that pull the key while the pull is in flight
+type PullReply<ValT> = ReplySync<Result<ValT, CacheDbError>>;
 
-                    self.parsed_bytes_total = self.to_parse_bytes_total;
-                    self.to_parse_bytes_total += 2;
-                }
-                // parsing protocol val size
-                3 if { tcp_read_size >= self.to_parse_bytes_total } => {
-                    key_valsize_raw.copy_from_slice(&buff[self.parsed_bytes_total..self.to_parse_bytes_total]);
-                    self.val_size = u16::from_be_bytes(key_valsize_raw);
-                    self.parsed_protocoll_segment += 1;
+// pull that is in flight, only the caller that sent it can give up on it (by request id)
+struct PendingPull<ValT> {
+    req_id: u32,
+    reply: Arc<PullReply<ValT>>,
+    // the key changed while the pull was in flight, so its val must not be added to the near cache
+    invalidated: bool,
+}
 
-                    self.parsed_bytes_total = self.to_parse_bytes_total;
-                    self.to_parse_bytes_total += usize::from(self.val_size);
-                }
-                // parsing protocol val
-                4 if { tcp_read_size >= self.to_parse_bytes_total } => {
-                    if self.val_size != 0 {
-                        obj.val = ValT::from_bytes(&buff[self.parsed_bytes_total..self.to_parse_bytes_total])?;
-                    }
-                    self.parsed_protocoll_segment = 0;
-                    self.parsed_bytes_total = self.to_parse_bytes_total;
+// request that has been sent but not been replied to yet
+enum InFlightReq<KeyT, ValT> {
+    // pull replies resolve the PendingPull of the key
+    Pull(KeyT),
+    Reply(Arc<ReplySync<ReplyFrame<KeyT, ValT>>>),
+}
 
-                    // self.parsed_bytes_total = 0;
-                    // self.to_parse_bytes_total = 0;
-                    return Ok((true, 0));
-                }
-                _ => {
-                    // using self.parsed_bytes_total because thats last matching expression and self.to_parse_bytes_total isn't parsed anymore
-                    let left_over_size = tcp_read_size - self.parsed_bytes_total;
+pub struct CacheDb<KeyT, ValT> {
+    // the server listens on all of them
+    addrs: Vec<SocketAddr>,
+
+    shards: Vec<Shard<KeyT, ValT>>,
+    shard_hasher: RandomState,
+    usage: Arc<StoreUsage>,
+
+    max_entries: Option<usize>,
+    max_bytes: Option<usize>,
+    evictions: AtomicU64,
+
+    frame_mode: FrameMode,
+    max_frame_size: usize,
+    server_identity: String,
+    handshake_timeout: Duration,
+    server_event_hook: Option<ServerEventHook>,
+    unix_socket_path: Option<PathBuf>,
+    write_timeout: Option<Duration>,
+    tracking: Mutex<KeyTracking<KeyT>>,
+    // number of keys in key_conns, so that changes do not lock the tracking if no key is tracked
+    tracked_keys: AtomicUsize,
+    // number of watched keys and prefixes, so that changes do not lock the tracking if nothing is watched
+    watches: AtomicUsize,
+}
+
+// client connections that are sent frames once a key changed
+struct KeyTracking<KeyT> {
+    next_conn_id: u64,
+    conns: HashMap<u64, TrackedConn<KeyT>>,
+    // keys pulled by clients with a near cache, a key is tracked until it changed
+    key_conns: HashMap<KeyT, HashSet<u64>>,
+    // watches are removed by an unwatch or once the connection is closed
+    key_watchers: HashMap<KeyT, HashSet<u64>>,
+    prefix_watchers: HashMap<Vec<u8>, HashSet<u64>>,
+}
 
-                    if left_over_size > 0 {
-                        // cloning to new vec because "inside buff copy" or move would require a mut and non mut ref
-                        let mut left_over_buff: Vec<u8> = vec![];
-                        left_over_buff.extend_from_slice(&buff[(tcp_read_size-left_over_size)..]);
+struct TrackedConn<KeyT> {
+    // InvalidateOps and ChangeNotifyOps are written by the outbound thread of the connection, so that changes never wait for a client
+    // None until the connection tracks its first key or watch
+    outbound: Option<mpsc::SyncSender<Vec<u8>>>,
+    // shared with the client handler, so that the frames of the outbound thread do not interleave with the replies
+    writer: Arc<Mutex<ConnStream>>,
+    // closes the connection once the outbound queue is full
+    closer: Arc<ConnStream>,
+    keys: HashSet<KeyT>,
+    watched_keys: HashSet<KeyT>,
+    watched_prefixes: HashSet<Vec<u8>>,
+}
 
-                        // copying left over to the beginning of the array. (will not be overwritten at nect tcp read!)
-                        buff[..left_over_size].copy_from_slice(&left_over_buff);
-                    }
-                    return Ok((false, left_over_size));
+impl<KeyT> TrackedConn<KeyT> {
+    // starts the outbound thread, it stops once the connection is untracked
+    fn start_outbound(&mut self) {
+        if self.outbound.is_some() {
+            return;
+        }
+        let (outbound, outbound_frames) = mpsc::sync_channel::<Vec<u8>>(CACHE_DB_OUTBOUND_QUEUE_SIZE);
+        let writer = Arc::clone(&self.writer);
+        thread::spawn(move || {
+            for send_buff in outbound_frames {
+                let mut writer = writer.lock().unwrap();
+                if writer.write_all(&send_buff).is_err() {
+                    let _ = writer.shutdown(Shutdown::Both);
+                    return;
                 }
             }
+        });
+        self.outbound = Some(outbound);
+    }
+
+    // the frames are added to the send_buffs of the connection, connections without outbound thread are skipped
+    fn queue_frame(&self, conn_id: u64, frame: &[u8], send_buffs: &mut HashMap<u64, TrackedSend>) {
+        if let Some(outbound) = &self.outbound {
+            send_buffs.entry(conn_id).or_insert_with(|| (outbound.clone(), Arc::clone(&self.closer), Vec::new())).2.extend_from_slice(frame);
         }
     }
 }
 
-impl<KeyT: 'static, ValT: 'static> CacheClient<KeyT, ValT> where KeyT: GenericKeyVal<KeyT> + Clone + PartialEq + Default + Debug + Send + Sync, ValT: GenericKeyVal<ValT> + Clone + Debug + Default + Send + Sync {
+// frames for a tracked connection and the means to close it
+type TrackedSend = (mpsc::SyncSender<Vec<u8>>, Arc<ConnStream>, Vec<u8>);
 
-    pub fn create_connect(ipv4_addr: [u8; 4], port: u16) -> Result<Arc<CacheClient<KeyT, ValT>>, std::io::Error> {
-        let addr = SocketAddr::from((ipv4_addr, port));
-        let tcp_stream = TcpStream::connect(addr)?;
-        Ok(Arc::new(CacheClient {
-            tcp_conn: RwLock::new(tcp_stream),
-            key_val_sync_store: RwLock::new(Vec::new()),
+// removes the conn_id from the conn_ids of the key, keys without connections are removed
+fn remove_conn_id<K: Hash + Eq>(conns_by_key: &mut HashMap<K, HashSet<u64>>, key: &K, conn_id: u64) {
+    if let Some(conn_ids) = conns_by_key.get_mut(key) {
+        conn_ids.remove(&conn_id);
+        if conn_ids.is_empty() {
+            conns_by_key.remove(key);
+        }
+    }
+}
 
-            pd_k: PhantomData,
-            pd_v: PhantomData
-        }))
+// address of either end of a connection
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum ConnAddr {
+    Tcp(SocketAddr),
+    // None for unnamed sockets, such as the ones of clients
+    #[cfg(unix)]
+    Unix(Option<PathBuf>),
+}
+
+// stream of a connection, the CacheProtocol framing is the same for all transports
+enum ConnStream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl ConnStream {
+    // the timeout only applies to tcp, unix sockets connect right away
+    fn connect(addr: &ConnAddr, timeout: Option<Duration>) -> io::Result<ConnStream> {
+        match addr {
+            ConnAddr::Tcp(addr) => match timeout {
+                Some(timeout) => Ok(ConnStream::Tcp(TcpStream::connect_timeout(addr, timeout)?)),
+                None => Ok(ConnStream::Tcp(TcpStream::connect(addr)?)),
+            },
+            #[cfg(unix)]
+            ConnAddr::Unix(Some(path)) => Ok(ConnStream::Unix(UnixStream::connect(path)?)),
+            #[cfg(unix)]
+            ConnAddr::Unix(None) => Err(io::Error::from(io::ErrorKind::InvalidInput)),
+        }
+    }
+
+    fn try_clone(&self) -> io::Result<ConnStream> {
+        match self {
+            ConnStream::Tcp(stream) => Ok(ConnStream::Tcp(stream.try_clone()?)),
+            #[cfg(unix)]
+            ConnStream::Unix(stream) => Ok(ConnStream::Unix(stream.try_clone()?)),
+        }
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            ConnStream::Tcp(stream) => stream.shutdown(how),
+            #[cfg(unix)]
+            ConnStream::Unix(stream) => stream.shutdown(how),
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            ConnStream::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            ConnStream::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            ConnStream::Tcp(stream) => stream.set_write_timeout(timeout),
+            #[cfg(unix)]
+            ConnStream::Unix(stream) => stream.set_write_timeout(timeout),
+        }
+    }
+}
+
+impl Read for ConnStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            ConnStream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            ConnStream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for ConnStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ConnStream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            ConnStream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ConnStream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            ConnStream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+enum ConnListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl ConnListener {
+    fn accept(&self) -> io::Result<(ConnStream, ConnAddr)> {
+        match self {
+            ConnListener::Tcp(listener) => listener.accept().map(|(stream, addr)| (ConnStream::Tcp(stream), ConnAddr::Tcp(addr))),
+            #[cfg(unix)]
+            ConnListener::Unix(listener) => listener.accept().map(|(stream, addr)| (ConnStream::Unix(stream), ConnAddr::Unix(addr.as_pathname().map(Path::to_path_buf)))),
+        }
+    }
+
+    // a socket file that does not accept connections has been left behind by a server that has not been shut down and is replaced
+    #[cfg(unix)]
+    fn bind_unix(path: &Path) -> io::Result<ConnListener> {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, "unix socket path is not a socket"));
+            }
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::from(io::ErrorKind::AddrInUse));
+            }
+            fs::remove_file(path)?;
+        }
+        Ok(ConnListener::Unix(UnixListener::bind(path)?))
+    }
+
+    #[cfg(not(unix))]
+    fn bind_unix(_path: &Path) -> io::Result<ConnListener> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+// why the server closed a client connection
+#[derive(PartialEq, Clone, Debug)]
+pub enum ConnCloseReason {
+    // the client sent TerminateConn
+    Terminated,
+    // the client closed the connection
+    Eof,
+    // the server has been shut down through the ServerHandle
+    Shutdown,
+    // reading from or writing to the socket failed
+    IoError(io::ErrorKind),
+    // the client did not send a hello or is not compatible
+    HandshakeFailed(CacheDbError),
+    // the client sent data that could not be parsed into frames
+    ProtocolError(CacheDbError),
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum ServerEvent {
+    // a client completed the hello exchange
+    Connected(ConnAddr),
+    // a request of the client could not be handled, the client received an error reply and the connection stays open
+    FrameError(ConnAddr, CacheDbError),
+    Closed(ConnAddr, ConnCloseReason),
+}
+
+// called by the client handler threads for every ServerEvent
+pub type ServerEventHook = Arc<dyn Fn(&ServerEvent) + Send + Sync>;
+
+// encoding of the key and val sizes, both ends of a connection must use the same mode
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum FrameMode {
+    // sizes are encoded as u16, which limits keys and vals to 64 KiB
+    Standard,
+    // sizes are encoded as u32
+    Large,
+}
+
+impl FrameMode {
+    fn capabilities(&self) -> u32 {
+        match self {
+            FrameMode::Standard => 0,
+            FrameMode::Large => CACHE_PROTOCOL_CAP_LARGE_FRAMES,
+        }
+    }
+}
+
+// first frame sent by the client (HelloOp) and its reply by the server (HelloReplyOp)
+// its layout does not depend on the frame mode or the key/val types so that it can always be read:
+// [op_code u8][req_id u32][version u16][capabilities u32][identity_size u16][identity]
+#[derive(PartialEq, Clone, Debug)]
+pub struct ProtHello {
+    pub version: u16,
+    // CACHE_PROTOCOL_CAP_* bits
+    pub capabilities: u32,
+    // name and version of the client or server software
+    pub identity: String,
+}
+
+impl ProtHello {
+    fn new(frame_mode: FrameMode, identity: String) -> ProtHello {
+        ProtHello {
+            version: CACHE_PROTOCOL_VERSION,
+            capabilities: CACHE_PROTOCOL_CAP_TTL | frame_mode.capabilities(),
+            identity,
+        }
+    }
+
+    pub fn assemble_buff(&self, op_code: ProtOpCode) -> Result<Vec<u8>, CacheDbError> {
+        let identity_size = u16::try_from(self.identity.len()).map_err(|_| CacheDbError::ProtocolSizeBufferOverflow)?;
+        let mut buff = vec![op_code as u8];
+        buff.extend_from_slice(&0u32.to_be_bytes());
+        buff.extend_from_slice(&self.version.to_be_bytes());
+        buff.extend_from_slice(&self.capabilities.to_be_bytes());
+        buff.extend_from_slice(&identity_size.to_be_bytes());
+        buff.extend_from_slice(self.identity.as_bytes());
+        Ok(buff)
+    }
+
+    // blocks until a whole hello frame with the expected op_code has been read, fails with NetworkTimeOutError if the read timeout of the reader expired
+    pub fn read_hello(reader: &mut impl Read, op_code: ProtOpCode) -> Result<ProtHello, CacheDbError> {
+        let mut header = [0u8; 13];
+        reader.read_exact(&mut header).map_err(|e| CacheDbError::from_io_error(&e))?;
+        if header[0] != op_code as u8 {
+            return Err(CacheDbError::ParsingErr);
+        }
+        let version = u16::from_be_bytes([header[5], header[6]]);
+        let capabilities = u32::from_be_bytes([header[7], header[8], header[9], header[10]]);
+        let mut identity = vec![0u8; usize::from(u16::from_be_bytes([header[11], header[12]]))];
+        reader.read_exact(&mut identity).map_err(|e| CacheDbError::from_io_error(&e))?;
+        let identity = String::from_utf8(identity).map_err(|_| CacheDbError::DecodingErr)?;
+        Ok(ProtHello{version, capabilities, identity})
+    }
+
+    // checks wether a client hello is compatible with the servers hello
+    fn check_compatible(&self, server_hello: &ProtHello) -> Result<(), CacheDbError> {
+        if self.version != server_hello.version {
+            return Err(CacheDbError::ProtocolVersionMismatch);
+        }
+        // both ends have to agree on the frame mode, all other capabilities of the client must be supported by the server
+        let frame_mode_mismatch = (self.capabilities ^ server_hello.capabilities) & CACHE_PROTOCOL_CAP_LARGE_FRAMES != 0;
+        if frame_mode_mismatch || self.capabilities & !server_hello.capabilities != 0 {
+            return Err(CacheDbError::CapabilityMismatch);
+        }
+        Ok(())
+    }
+}
+
+pub struct CacheProtocol<KeyT, ValT> {
+    frame_mode: FrameMode,
+    max_frame_size: usize,
+    // received data that has not been parsed yet
+    buff: Vec<u8>,
+    // request id and ttl (in ms, 0 if there is none) of the last parsed frame
+    req_id: u32,
+    ttl_ms: u64,
+    // error of the last parsed ErrorReplyOp
+    error: Option<CacheDbError>,
+    // entries of the last parsed multi frame
+    entries: Vec<(KeyValObj<KeyT, ValT>, bool)>,
+    // found, key pos, key size, val pos, val size of the entries of the multi frame that has not been received completely,
+    // kept between the feeds so that the entries are only scanned once
+    multi_entry_positions: Vec<(bool, usize, usize, usize, usize)>,
+    // end of the last scanned entry
+    multi_scanned_size: usize,
+
+    // because of unconstrained type conflict
+    pd_k: PhantomData<KeyT>,
+    pd_v: PhantomData<ValT>,
+}
+
+// decides wether push, push_with_ttl and push_with_mode wait for the server to store the obj
+// acknowledged pushes that have not been stored because of the push mode fail with NotStored
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum WriteConcern {
+    // returns once the push has been written to the socket
+    Unacknowledged,
+    // waits for the PushAckOp of the server (up to ack_timeout)
+    Acknowledged,
+}
+
+// how the cache_client_handler reconnects once the connection to the server has been lost
+#[derive(Clone, Debug)]
+pub struct ReconnectPolicy {
+    // attempts after which the client gives up
+    pub max_retries: u32,
+    // backoff before the first attempt, doubled for every following attempt
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // fraction (0 to 1) of the backoff that is randomly subtracted, so that clients do not reconnect at the same time
+    pub jitter: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(5),
+            jitter: 0.5,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    // attempt starts at 1, rand is in [0, 1)
+    fn backoff(&self, attempt: u32, rand: f64) -> Duration {
+        let backoff = self.initial_backoff.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1))).min(self.max_backoff);
+        backoff.mul_f64(1.0 - self.jitter.clamp(0.0, 1.0) * rand)
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum ConnState {
+    // the connection to the server has been lost
+    Disconnected(CacheDbError),
+    // waiting for backoff before the attempt
+    Reconnecting { attempt: u32, backoff: Duration },
+    // the client is connected again, pulls that were in flight have been re-issued
+    Reconnected,
+    // the retry budget has been exhausted, the client stays disconnected
+    Failed(CacheDbError),
+}
+
+// called by the cache_client_handler for every change of the ConnState
+pub type ConnStateHook = Arc<dyn Fn(&ConnState) + Send + Sync>;
+
+// key or key prefix (compared by the bytes of the keys) that is watched for changes
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum WatchTarget<KeyT> {
+    Key(KeyT),
+    Prefix(KeyT),
+}
+
+// change of a watched key, pushed by the server (ChangeNotifyOp and ChangeNotifyDeletedOp)
+#[derive(PartialEq, Clone, Debug)]
+pub enum WatchEvent<KeyT, ValT> {
+    // the key has been pushed or set, carries the new val
+    Changed(KeyT, ValT),
+    Deleted(KeyT),
+}
+
+// called by the cache_client_handler for every WatchEvent of the watch, must not wait for replies of the same client
+pub type WatchCallback<KeyT, ValT> = Arc<dyn Fn(&WatchEvent<KeyT, ValT>) + Send + Sync>;
+
+enum WatchSink<KeyT, ValT> {
+    Channel(mpsc::Sender<WatchEvent<KeyT, ValT>>),
+    Callback(WatchCallback<KeyT, ValT>),
+}
+
+// the sinks of a target are identified by an id, so that a failed watch only removes its own sink
+type Watches<KeyT, ValT> = HashMap<WatchTarget<KeyT>, Vec<(u64, WatchSink<KeyT, ValT>)>>;
+
+// local copy of pulled vals, which are dropped once the server invalidates them
+// expired keys are only invalidated once the server swept them
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct NearCacheConfig {
+    // the least recently used entry is dropped once the near cache is full
+    pub max_entries: usize,
+    // max time a val is served without asking the server
+    pub ttl: Duration,
+}
+
+impl Default for NearCacheConfig {
+    fn default() -> Self {
+        NearCacheConfig {
+            max_entries: 10_000,
+            ttl: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub struct NearCacheStats {
+    pub entries: usize,
+    // pulls that have been served by the near cache
+    pub hits: u64,
+    pub misses: u64,
+    // entries dropped because of an InvalidateOp of the server
+    pub invalidations: u64,
+}
+
+// pulled vals of a CacheClient, the cache_client_handler fills it with pull replies
+struct NearCache<KeyT, ValT> {
+    config: NearCacheConfig,
+    // val and the point in time it expires at
+    entries: HashMap<KeyT, (ValT, Instant)>,
+    lru: LruPolicy<KeyT>,
+    stats: NearCacheStats,
+}
+
+impl<KeyT: Hash + Eq + Clone + Send, ValT: Clone> NearCache<KeyT, ValT> {
+    fn new(config: NearCacheConfig) -> NearCache<KeyT, ValT> {
+        NearCache { config, entries: HashMap::new(), lru: LruPolicy::new(), stats: NearCacheStats::default() }
+    }
+
+    fn get(&mut self, key: &KeyT, now: Instant) -> Option<ValT> {
+        let val = match self.entries.get(key) {
+            Some((val, expires_at)) if *expires_at > now => Some(val.clone()),
+            Some(_) => {
+                self.remove(key);
+                None
+            }
+            None => None
+        };
+        match val {
+            Some(_) => {
+                self.stats.hits += 1;
+                self.lru.on_access(key);
+            }
+            None => self.stats.misses += 1,
+        }
+        val
+    }
+
+    fn insert(&mut self, key: KeyT, val: ValT, now: Instant) {
+        if self.config.max_entries == 0 {
+            return;
+        }
+        self.lru.on_insert(&key);
+        self.entries.insert(key, (val, now + self.config.ttl));
+        while self.entries.len() > self.config.max_entries {
+            match self.lru.victim() {
+                Some(victim) => {
+                    self.entries.remove(&victim);
+                }
+                None => break
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &KeyT) -> bool {
+        self.lru.on_remove(key);
+        self.entries.remove(key).is_some()
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.lru = LruPolicy::new();
+    }
+
+    fn stats(&self) -> NearCacheStats {
+        NearCacheStats{entries: self.entries.len(), ..self.stats}
+    }
+}
+
+#[derive(Clone)]
+pub struct CacheClientConfig {
+    // has to match the frame mode of the server
+    pub frame_mode: FrameMode,
+    pub max_frame_size: usize,
+    pub write_concern: WriteConcern,
+    // max wait for the ack of an acknowledged push
+    pub ack_timeout: Duration,
+    // max wait per resolved address, None waits as long as the os does
+    pub connect_timeout: Option<Duration>,
+    // max wait for the hello reply and for data while requests are in flight, the connection is treated as lost once it stalled
+    pub read_timeout: Option<Duration>,
+    // a write that takes longer fails and closes the connection, since the frame may have been written partially
+    pub write_timeout: Option<Duration>,
+    // max wait for the reply of pull, delete, ttl and pull_many, the _with_deadline methods override it per call
+    pub request_timeout: Duration,
+    // the client does not reconnect if None
+    pub reconnect_policy: Option<ReconnectPolicy>,
+    pub conn_state_hook: Option<ConnStateHook>,
+    // pulls are served locally if set, pull_many always asks the server
+    pub near_cache: Option<NearCacheConfig>,
+}
+
+impl Default for CacheClientConfig {
+    fn default() -> Self {
+        CacheClientConfig {
+            frame_mode: FrameMode::Standard,
+            max_frame_size: CACHE_PROTOCOL_MAX_FRAME_SIZE,
+            write_concern: WriteConcern::Unacknowledged,
+            ack_timeout: CACHE_CLIENT_DEFAULT_TIMEOUT,
+            connect_timeout: None,
+            read_timeout: Some(CACHE_CLIENT_DEFAULT_TIMEOUT),
+            write_timeout: None,
+            request_timeout: CACHE_CLIENT_DEFAULT_TIMEOUT,
+            reconnect_policy: None,
+            conn_state_hook: None,
+            near_cache: None,
+        }
+    }
+}
+
+// how the CacheClientPool picks the connection of a request
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum PoolDispatch {
+    // every request uses the next connection
+    RoundRobin,
+    // requests of the same key always use the same connection, batches are dispatched round robin
+    KeyHash,
+}
+
+#[derive(Clone)]
+pub struct CacheClientPoolConfig {
+    // number of connections
+    pub size: usize,
+    pub dispatch: PoolDispatch,
+    // used for every connection
+    pub client_config: CacheClientConfig,
+}
+
+impl Default for CacheClientPoolConfig {
+    fn default() -> Self {
+        CacheClientPoolConfig {
+            size: 4,
+            dispatch: PoolDispatch::RoundRobin,
+            client_config: CacheClientConfig::default(),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub struct CacheClientPoolStats {
+    pub size: usize,
+    // connections that are not lost or reconnecting
+    pub connected: usize,
+    // keys that are being pulled by the pool
+    pub pulls_in_flight: usize,
+    // pulls that waited for the pull of another caller instead of sending their own
+    pub deduplicated_pulls: u64,
+}
+
+// manages multiple connections to the same server, which are written and read concurrently
+pub struct CacheClientPool<KeyT, ValT> {
+    clients: Vec<Arc<CacheClient<KeyT, ValT>>>,
+    client_handlers: Vec<JoinHandle<Result<(), CacheDbError>>>,
+    dispatch: PoolDispatch,
+    next_client: AtomicUsize,
+    key_hasher: RandomState,
+    // pulls are shared by all callers of the pool, not only the ones using the same connection
+    pulls: Mutex<HashMap<KeyT, Arc<PullReply<ValT>>>>,
+    deduplicated_pulls: AtomicU64,
+    request_timeout: Duration,
+}
+
+#[derive(Clone)]
+pub struct CacheClusterConfig {
+    // points per node on the hash ring, more points spread the keys more evenly
+    pub virtual_nodes: usize,
+    // used for the connection to every node
+    pub client_config: CacheClientConfig,
+}
+
+impl Default for CacheClusterConfig {
+    fn default() -> Self {
+        CacheClusterConfig {
+            virtual_nodes: 160,
+            client_config: CacheClientConfig::default(),
+        }
+    }
+}
+
+// consistent hash ring, a key belongs to the first point at or after its hash
+// adding or removing a node only moves the keys of its points
+struct HashRing {
+    points: BTreeMap<u64, SocketAddr>,
+    virtual_nodes: usize,
+}
+
+impl HashRing {
+    fn new(virtual_nodes: usize) -> HashRing {
+        HashRing { points: BTreeMap::new(), virtual_nodes: virtual_nodes.max(1) }
+    }
+
+    // fnv-1a with a final mix, the hash has to be the same for every client process
+    fn hash(data: &[u8]) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in data {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+        hash ^= hash >> 33;
+        hash
+    }
+
+    fn add(&mut self, node: SocketAddr) {
+        for i in 0..self.virtual_nodes {
+            self.points.insert(HashRing::hash(format!("{}#{}", node, i).as_bytes()), node);
+        }
+    }
+
+    fn remove(&mut self, node: &SocketAddr) {
+        self.points.retain(|_, point_node| point_node != node);
+    }
+
+    fn node(&self, key: &[u8]) -> Option<SocketAddr> {
+        let hash = HashRing::hash(key);
+        self.points.range(hash..).next().or_else(|| self.points.iter().next()).map(|(_, node)| *node)
+    }
+}
+
+struct ClusterNodes<KeyT, ValT> {
+    ring: HashRing,
+    clients: HashMap<SocketAddr, ClusterNode<KeyT, ValT>>,
+}
+
+struct ClusterNode<KeyT, ValT> {
+    client: Arc<CacheClient<KeyT, ValT>>,
+    // joined once the node has been removed
+    client_handler: JoinHandle<Result<(), CacheDbError>>,
+}
+
+// client of a node and the indices of the keys that belong to it
+type NodeGroup<KeyT, ValT> = (Arc<CacheClient<KeyT, ValT>>, Vec<usize>);
+
+// routes every key to one of multiple CacheDb servers
+pub struct CacheClusterClient<KeyT, ValT> {
+    nodes: RwLock<ClusterNodes<KeyT, ValT>>,
+    client_config: CacheClientConfig,
+}
+
+pub struct CacheClient<KeyT, ValT> {
+    // pulls that are in flight by key, removed once the reply arrived
+    pulls: Mutex<HashMap<KeyT, PendingPull<ValT>>>,
+    // requests waiting for their reply by request id
+    in_flight: Mutex<HashMap<u32, InFlightReq<KeyT, ValT>>>,
+    next_req_id: AtomicU32,
+    // replaced on reconnect
+    conn: RwLock<ConnStream>,
+    // resolved on connect, tried in order on reconnect
+    server_addrs: Vec<ConnAddr>,
+    frame_mode: FrameMode,
+    max_frame_size: usize,
+    write_concern: WriteConcern,
+    ack_timeout: Duration,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    request_timeout: Duration,
+    server_hello: RwLock<ProtHello>,
+    reconnect_policy: Option<ReconnectPolicy>,
+    conn_state_hook: Option<ConnStateHook>,
+    // set once the connection has been terminated by the client or could not be reestablished
+    closed: AtomicBool,
+    // false while the cache_client_handler is reconnecting
+    connected: AtomicBool,
+    near_cache: Option<Mutex<NearCache<KeyT, ValT>>>,
+    // all watches are dropped once the connection is lost, since changes might have been missed
+    watches: Mutex<Watches<KeyT, ValT>>,
+    next_watch_id: AtomicU64,
+
+    // because of unconstrained type conflict
+    pd_k: PhantomData<KeyT>,
+    pd_v: PhantomData<ValT>,
+}
+
+pub trait GenericKeyVal<Val> {
+    fn get_size(&self) -> Result<u32, CacheDbError>;
+    fn get_bytes(&self) -> Vec<u8>;
+    fn from_bytes(data: &[u8]) -> Result<Val, CacheDbError>;
+}
+
+impl<KeyT, ValT> Default for CacheProtocol<KeyT, ValT> where KeyT: GenericKeyVal<KeyT> + Default, ValT: GenericKeyVal<ValT> + Default + Debug {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<KeyT, ValT> CacheProtocol<KeyT, ValT> where KeyT: GenericKeyVal<KeyT> + Default, ValT: GenericKeyVal<ValT> + Default + Debug {
+    fn prot_op_code_to_u8_be(op_code: &ProtOpCode) -> u8 {
+        match op_code {
+            ProtOpCode::PullOp => u8::from_le(1),
+            ProtOpCode::PushOp => u8::from_le(2),
+            ProtOpCode::PullReplyOp => u8::from_le(3),
+            ProtOpCode::PullReplyNotFoundOp => u8::from_le(4),
+            ProtOpCode::TerminateConn => u8::from_le(5),
+            ProtOpCode::PushInsertOp => u8::from_le(6),
+            ProtOpCode::PushUpdateOp => u8::from_le(7),
+            ProtOpCode::DeleteOp => u8::from_le(8),
+            ProtOpCode::DeleteReplyOp => u8::from_le(9),
+            ProtOpCode::DeleteReplyNotFoundOp => u8::from_le(10),
+            ProtOpCode::TtlOp => u8::from_le(11),
+            ProtOpCode::TtlReplyOp => u8::from_le(12),
+            ProtOpCode::TtlReplyNotFoundOp => u8::from_le(13),
+            ProtOpCode::HelloOp => u8::from_le(14),
+            ProtOpCode::HelloReplyOp => u8::from_le(15),
+            ProtOpCode::ErrorReplyOp => u8::from_le(16),
+            ProtOpCode::PushAckOp => u8::from_le(17),
+            ProtOpCode::PushAckNotStoredOp => u8::from_le(18),
+            ProtOpCode::MultiPullOp => u8::from_le(19),
+            ProtOpCode::MultiPullReplyOp => u8::from_le(20),
+            ProtOpCode::MultiPushOp => u8::from_le(21),
+            ProtOpCode::InvalidateOp => u8::from_le(22),
+            ProtOpCode::WatchOp => u8::from_le(23),
+            ProtOpCode::WatchPrefixOp => u8::from_le(24),
+            ProtOpCode::UnwatchOp => u8::from_le(25),
+            ProtOpCode::UnwatchPrefixOp => u8::from_le(26),
+            ProtOpCode::WatchAckOp => u8::from_le(27),
+            ProtOpCode::ChangeNotifyOp => u8::from_le(28),
+            ProtOpCode::ChangeNotifyDeletedOp => u8::from_le(29),
+        }
+    }
+    fn u8_to_prot_op_code_le(op_code: u8) -> Option<ProtOpCode> {
+        match u8::from_be(op_code) {
+            1 => Some(ProtOpCode::PullOp),
+            2 => Some(ProtOpCode::PushOp),
+            3 => Some(ProtOpCode::PullReplyOp),
+            4 => Some(ProtOpCode::PullReplyNotFoundOp),
+            5 => Some(ProtOpCode::TerminateConn),
+            6 => Some(ProtOpCode::PushInsertOp),
+            7 => Some(ProtOpCode::PushUpdateOp),
+            8 => Some(ProtOpCode::DeleteOp),
+            9 => Some(ProtOpCode::DeleteReplyOp),
+            10 => Some(ProtOpCode::DeleteReplyNotFoundOp),
+            11 => Some(ProtOpCode::TtlOp),
+            12 => Some(ProtOpCode::TtlReplyOp),
+            13 => Some(ProtOpCode::TtlReplyNotFoundOp),
+            14 => Some(ProtOpCode::HelloOp),
+            15 => Some(ProtOpCode::HelloReplyOp),
+            16 => Some(ProtOpCode::ErrorReplyOp),
+            17 => Some(ProtOpCode::PushAckOp),
+            18 => Some(ProtOpCode::PushAckNotStoredOp),
+            19 => Some(ProtOpCode::MultiPullOp),
+            20 => Some(ProtOpCode::MultiPullReplyOp),
+            21 => Some(ProtOpCode::MultiPushOp),
+            22 => Some(ProtOpCode::InvalidateOp),
+            23 => Some(ProtOpCode::WatchOp),
+            24 => Some(ProtOpCode::WatchPrefixOp),
+            25 => Some(ProtOpCode::UnwatchOp),
+            26 => Some(ProtOpCode::UnwatchPrefixOp),
+            27 => Some(ProtOpCode::WatchAckOp),
+            28 => Some(ProtOpCode::ChangeNotifyOp),
+            29 => Some(ProtOpCode::ChangeNotifyDeletedOp),
+            _ => None,
+        }
+    }
+
+    // only push and ttl reply frames contain a ttl segment (following the op_code)
+    fn op_has_ttl(op_code: &ProtOpCode) -> bool {
+        matches!(op_code, ProtOpCode::PushOp | ProtOpCode::PushInsertOp | ProtOpCode::PushUpdateOp | ProtOpCode::TtlReplyOp | ProtOpCode::MultiPushOp)
+    }
+
+    // multi frames carry a list of entries instead of a single key and val
+    fn op_is_multi(op_code: &ProtOpCode) -> bool {
+        matches!(op_code, ProtOpCode::MultiPullOp | ProtOpCode::MultiPullReplyOp | ProtOpCode::MultiPushOp)
+    }
+
+    fn ttl_to_ms(ttl: Option<Duration>) -> u64 {
+        match ttl {
+            // 0 is reserved for "no ttl", so every ttl is at least 1ms
+            Some(ttl) => u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX).max(1),
+            None => 0,
+        }
+    }
+
+    // returns the request id of the last parsed frame, replies carry the id of their request
+    pub fn parsed_req_id(&self) -> u32 {
+        self.req_id
+    }
+
+    // returns the entries of the last parsed multi frame, the bool is the found flag of MultiPullReplyOp entries
+    pub fn take_parsed_entries(&mut self) -> Vec<(KeyValObj<KeyT, ValT>, bool)> {
+        std::mem::take(&mut self.entries)
+    }
+
+    // returns the error of the last parsed frame if it is an ErrorReplyOp
+    pub fn parsed_error(&self) -> Option<CacheDbError> {
+        self.error.clone()
+    }
+
+    // returns the ttl of the last parsed frame
+    pub fn parsed_ttl(&self) -> Option<Duration> {
+        match self.ttl_ms {
+            0 => None,
+            ttl_ms => Some(Duration::from_millis(ttl_ms)),
+        }
+    }
+
+    fn append_size(frame_mode: FrameMode, buff: &mut Vec<u8>, size: u32) -> Result<(), CacheDbError> {
+        match frame_mode {
+            FrameMode::Standard => {
+                let size = u16::try_from(size).map_err(|_| CacheDbError::ProtocolSizeBufferOverflow)?;
+                buff.extend_from_slice(&size.to_be_bytes());
+            }
+            FrameMode::Large => buff.extend_from_slice(&size.to_be_bytes()),
+        }
+        Ok(())
+    }
+
+    // ttl is ignored for frames without ttl segment
+    // frames that are not sent in reply to a request (such as TerminateConn by the server) use req_id 0
+    pub fn assemble_buff(frame_mode: FrameMode, op_code: ProtOpCode, req_id: u32, obj: &KeyValObj<KeyT, ValT>, ttl: Option<Duration>) -> Result<Vec<u8>, CacheDbError> {
+        let mut buff = Vec::<u8>::new();
+        buff.push(CacheProtocol::<KeyT, ValT>::prot_op_code_to_u8_be(&op_code));
+        buff.extend_from_slice(&req_id.to_be_bytes());
+        if CacheProtocol::<KeyT, ValT>::op_has_ttl(&op_code) {
+            buff.extend_from_slice(&CacheProtocol::<KeyT, ValT>::ttl_to_ms(ttl).to_be_bytes());
+        }
+        CacheProtocol::<KeyT, ValT>::append_size(frame_mode, &mut buff, obj.key.get_size()?)?;
+        let mut key_bytes = obj.key.get_bytes();
+        buff.append(&mut key_bytes);
+
+        // requests that only consist of a key are sent with an empty val
+        let has_val = !matches!(op_code, ProtOpCode::PullOp | ProtOpCode::DeleteOp | ProtOpCode::DeleteReplyOp | ProtOpCode::DeleteReplyNotFoundOp |
+            ProtOpCode::TtlOp | ProtOpCode::TtlReplyOp | ProtOpCode::TtlReplyNotFoundOp | ProtOpCode::TerminateConn |
+            ProtOpCode::PushAckOp | ProtOpCode::PushAckNotStoredOp | ProtOpCode::InvalidateOp | ProtOpCode::WatchOp | ProtOpCode::WatchPrefixOp |
+            ProtOpCode::UnwatchOp | ProtOpCode::UnwatchPrefixOp | ProtOpCode::WatchAckOp | ProtOpCode::ChangeNotifyDeletedOp);
+        if has_val {
+            CacheProtocol::<KeyT, ValT>::append_size(frame_mode, &mut buff, obj.val.get_size()?)?;
+            let mut val_bytes = obj.val.get_bytes();
+            buff.append(&mut val_bytes);
+        } else {
+            CacheProtocol::<KeyT, ValT>::append_size(frame_mode, &mut buff, 0)?;
+        }
+
+        Ok(buff)
+    }
+
+    // error replies have an error segment following the req_id instead of a key and val:
+    // [op_code u8][req_id u32][error_code u16][message_size u16][message] followed by key and val size 0
+    pub fn assemble_error_reply(frame_mode: FrameMode, req_id: u32, error: &CacheDbError, message: &str) -> Result<Vec<u8>, CacheDbError> {
+        let message_size = u16::try_from(message.len()).map_err(|_| CacheDbError::ProtocolSizeBufferOverflow)?;
+        let mut buff = vec![CacheProtocol::<KeyT, ValT>::prot_op_code_to_u8_be(&ProtOpCode::ErrorReplyOp)];
+        buff.extend_from_slice(&req_id.to_be_bytes());
+        buff.extend_from_slice(&error.error_code().to_be_bytes());
+        buff.extend_from_slice(&message_size.to_be_bytes());
+        buff.extend_from_slice(message.as_bytes());
+        CacheProtocol::<KeyT, ValT>::append_size(frame_mode, &mut buff, 0)?;
+        CacheProtocol::<KeyT, ValT>::append_size(frame_mode, &mut buff, 0)?;
+        Ok(buff)
+    }
+
+    // multi frames have a list of entries following the req_id (and ttl):
+    // [op_code u8][req_id u32][ttl u64 (MultiPushOp only)][count u32] count * ([found u8][key_size][key][val_size][val])
+    // MultiPullOp entries and entries that are not found have no val, found is always true for MultiPushOp entries
+    pub fn assemble_multi_buff(frame_mode: FrameMode, op_code: ProtOpCode, req_id: u32, entries: &[(KeyValObj<KeyT, ValT>, bool)], ttl: Option<Duration>) -> Result<Vec<u8>, CacheDbError> {
+        let count = u32::try_from(entries.len()).map_err(|_| CacheDbError::ProtocolSizeBufferOverflow)?;
+        let mut buff = vec![CacheProtocol::<KeyT, ValT>::prot_op_code_to_u8_be(&op_code)];
+        buff.extend_from_slice(&req_id.to_be_bytes());
+        if CacheProtocol::<KeyT, ValT>::op_has_ttl(&op_code) {
+            buff.extend_from_slice(&CacheProtocol::<KeyT, ValT>::ttl_to_ms(ttl).to_be_bytes());
+        }
+        buff.extend_from_slice(&count.to_be_bytes());
+        for (obj, found) in entries.iter() {
+            let found = *found || op_code == ProtOpCode::MultiPushOp;
+            buff.push(u8::from(found));
+            CacheProtocol::<KeyT, ValT>::append_size(frame_mode, &mut buff, obj.key.get_size()?)?;
+            buff.append(&mut obj.key.get_bytes());
+            if found && op_code != ProtOpCode::MultiPullOp {
+                CacheProtocol::<KeyT, ValT>::append_size(frame_mode, &mut buff, obj.val.get_size()?)?;
+                buff.append(&mut obj.val.get_bytes());
+            } else {
+                CacheProtocol::<KeyT, ValT>::append_size(frame_mode, &mut buff, 0)?;
+            }
+        }
+        Ok(buff)
+    }
+
+    pub fn new() -> CacheProtocol<KeyT, ValT> {
+        CacheProtocol::with_frame_mode(FrameMode::Standard, CACHE_PROTOCOL_MAX_FRAME_SIZE)
+    }
+
+    // frames that are larger than max_frame_size are rejected
+    pub fn with_frame_mode(frame_mode: FrameMode, max_frame_size: usize) -> CacheProtocol<KeyT, ValT> {
+        CacheProtocol {
+            frame_mode,
+            max_frame_size,
+            buff: Vec::new(),
+            req_id: 0,
+            ttl_ms: 0,
+            error: None,
+            entries: Vec::new(),
+            multi_entry_positions: Vec::new(),
+            multi_scanned_size: 0,
+            pd_k: PhantomData,
+            pd_v: PhantomData,
+        }
+    }
+
+    // appends received data to the not yet parsed data
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buff.extend_from_slice(data);
+    }
+
+    // reads a key or val size at pos, None if it has not been received yet
+    fn read_size(&self, pos: usize) -> Option<usize> {
+        match self.frame_mode {
+            FrameMode::Standard => {
+                let size_raw = self.buff.get(pos..pos+2)?;
+                Some(usize::from(u16::from_be_bytes([size_raw[0], size_raw[1]])))
+            }
+            FrameMode::Large => {
+                let size_raw = self.buff.get(pos..pos+4)?;
+                Some(u32::from_be_bytes([size_raw[0], size_raw[1], size_raw[2], size_raw[3]]) as usize)
+            }
+        }
+    }
+
+    // things to notice: tcp data can come in at different sizes(only order is guaranteed - FIFO)
+    // so the fed data is accumulated until there is a whole frame to parse
+    // returns true if a frame has been parsed (into op_code and obj), false if more data needs to be fed
+    pub fn parse_buff(&mut self, op_code: &mut ProtOpCode, obj: &mut KeyValObj<KeyT, ValT>) -> Result<bool, CacheDbError> {
+        let size_len = match self.frame_mode {
+            FrameMode::Standard => 2,
+            FrameMode::Large => 4,
+        };
+
+        // parsing protocol op_code
+        let Some(op_code_raw) = self.buff.first() else {
+            return Ok(false);
+        };
+        let Some(parsed_op_code) = CacheProtocol::<KeyT, ValT>::u8_to_prot_op_code_le(*op_code_raw) else {
+            return Err(CacheDbError::ParsingErr);
+        };
+        // parsing protocol request id
+        let Some(req_id_raw) = self.buff.get(1..5) else {
+            return Ok(false);
+        };
+        let req_id = u32::from_be_bytes([req_id_raw[0], req_id_raw[1], req_id_raw[2], req_id_raw[3]]);
+        let mut frame_size = 5;
+
+        // parsing protocol ttl (only present for some op_codes)
+        let mut ttl_ms = 0;
+        if CacheProtocol::<KeyT, ValT>::op_has_ttl(&parsed_op_code) {
+            let Some(ttl_raw) = self.buff.get(frame_size..frame_size+8) else {
+                return Ok(false);
+            };
+            let mut ttl_bytes: [u8; 8] = [0; 8];
+            ttl_bytes.copy_from_slice(ttl_raw);
+            ttl_ms = u64::from_be_bytes(ttl_bytes);
+            frame_size += 8;
+        }
+
+        // parsing protocol error (only present for error replies)
+        let mut error = None;
+        if parsed_op_code == ProtOpCode::ErrorReplyOp {
+            let Some(error_raw) = self.buff.get(frame_size..frame_size+4) else {
+                return Ok(false);
+            };
+            let error_code = u16::from_be_bytes([error_raw[0], error_raw[1]]);
+            let message_size = usize::from(u16::from_be_bytes([error_raw[2], error_raw[3]]));
+            frame_size += 4;
+            let Some(message_raw) = self.buff.get(frame_size..frame_size+message_size) else {
+                return Ok(false);
+            };
+            error = Some(CacheDbError::from_error_code(error_code, String::from_utf8_lossy(message_raw).into_owned()));
+            frame_size += message_size;
+        }
+
+        if CacheProtocol::<KeyT, ValT>::op_is_multi(&parsed_op_code) {
+            return self.parse_multi(parsed_op_code, req_id, ttl_ms, frame_size, op_code);
+        }
+
+        // parsing protocol key size
+        let Some(key_size) = self.read_size(frame_size) else {
+            return Ok(false);
+        };
+        let key_pos = frame_size + size_len;
+        frame_size = key_pos + key_size;
+        if frame_size > self.max_frame_size {
+            return Err(CacheDbError::ProtocolSizeBufferOverflow);
+        }
+
+        // parsing protocol val size
+        let Some(val_size) = self.read_size(frame_size) else {
+            return Ok(false);
+        };
+        let val_pos = frame_size + size_len;
+        frame_size = val_pos + val_size;
+        if frame_size > self.max_frame_size {
+            return Err(CacheDbError::ProtocolSizeBufferOverflow);
+        }
+        if self.buff.len() < frame_size {
+            return Ok(false);
+        }
+
+        // the frame is removed before decoding, so a decoding error does not corrupt the following frames
+        let frame: Vec<u8> = self.buff.drain(..frame_size).collect();
+        op_code.clone_from(&parsed_op_code);
+        self.req_id = req_id;
+        self.ttl_ms = ttl_ms;
+        self.error = error;
+        // parsing protocol key, empty keys and vals are reset so that nothing of the previous frame is left in obj
+        obj.key = match key_size {
+            0 => KeyT::default(),
+            _ => KeyT::from_bytes(&frame[key_pos..key_pos+key_size])?,
+        };
+        // parsing protocol val
+        obj.val = match val_size {
+            0 => ValT::default(),
+            _ => ValT::from_bytes(&frame[val_pos..val_pos+val_size])?,
+        };
+        Ok(true)
+    }
+
+    // parses the entries of a multi frame starting at frame_size, see assemble_multi_buff
+    fn parse_multi(&mut self, parsed_op_code: ProtOpCode, req_id: u32, ttl_ms: u64, mut frame_size: usize, op_code: &mut ProtOpCode) -> Result<bool, CacheDbError> {
+        let size_len = match self.frame_mode {
+            FrameMode::Standard => 2,
+            FrameMode::Large => 4,
+        };
+        let Some(count_raw) = self.buff.get(frame_size..frame_size+4) else {
+            return Ok(false);
+        };
+        let count = u32::from_be_bytes([count_raw[0], count_raw[1], count_raw[2], count_raw[3]]) as usize;
+        frame_size += 4;
+
+        // the frame stays at the start of buff until it has been received completely, so the entries scanned by earlier calls are still valid
+        if !self.multi_entry_positions.is_empty() {
+            frame_size = self.multi_scanned_size;
+        }
+        while self.multi_entry_positions.len() < count {
+            let Some(found) = self.buff.get(frame_size).map(|found| *found != 0) else {
+                return Ok(false);
+            };
+            let Some(key_size) = self.read_size(frame_size + 1) else {
+                return Ok(false);
+            };
+            let key_pos = frame_size + 1 + size_len;
+            let Some(val_size) = self.read_size(key_pos + key_size) else {
+                return Ok(false);
+            };
+            let val_pos = key_pos + key_size + size_len;
+            frame_size = val_pos + val_size;
+            if frame_size > self.max_frame_size {
+                return Err(CacheDbError::ProtocolSizeBufferOverflow);
+            }
+            self.multi_entry_positions.push((found, key_pos, key_size, val_pos, val_size));
+            self.multi_scanned_size = frame_size;
+        }
+        if self.buff.len() < frame_size {
+            return Ok(false);
+        }
+        let entry_positions = std::mem::take(&mut self.multi_entry_positions);
+
+        // the frame is removed before decoding, so a decoding error does not corrupt the following frames
+        let frame: Vec<u8> = self.buff.drain(..frame_size).collect();
+        op_code.clone_from(&parsed_op_code);
+        self.req_id = req_id;
+        self.ttl_ms = ttl_ms;
+        self.error = None;
+        self.entries.clear();
+        for (found, key_pos, key_size, val_pos, val_size) in entry_positions {
+            let key = KeyT::from_bytes(&frame[key_pos..key_pos+key_size])?;
+            let val = match val_size {
+                0 => ValT::default(),
+                _ => ValT::from_bytes(&frame[val_pos..val_pos+val_size])?,
+            };
+            self.entries.push((KeyValObj{key, val}, found));
+        }
+        Ok(true)
+    }
+}
+
+impl<KeyT: 'static, ValT: 'static> CacheClient<KeyT, ValT> where KeyT: GenericKeyVal<KeyT> + Hash + Eq + Clone + Default + Debug + Send + Sync, ValT: GenericKeyVal<ValT> + Clone + Debug + Default + Send + Sync {
+
+    pub fn create_connect(ipv4_addr: [u8; 4], port: u16) -> Result<Arc<CacheClient<KeyT, ValT>>, CacheDbError> {
+        CacheClient::create_connect_with_config(ipv4_addr, port, CacheClientConfig::default())
+    }
+
+    // fails with ProtocolVersionMismatch or CapabilityMismatch if the server does not speak the clients protocol
+    pub fn create_connect_with_config(ipv4_addr: [u8; 4], port: u16, config: CacheClientConfig) -> Result<Arc<CacheClient<KeyT, ValT>>, CacheDbError> {
+        CacheClient::connect_with_config(SocketAddr::from((ipv4_addr, port)), config)
+    }
+
+    // addrs can be a hostname with port (such as "cache.internal:7000"), an ipv6 address or a list of SocketAddr
+    pub fn connect<A: ToSocketAddrs>(addrs: A) -> Result<Arc<CacheClient<KeyT, ValT>>, CacheDbError> {
+        CacheClient::connect_with_config(addrs, CacheClientConfig::default())
+    }
+
+    // every resolved address is tried until a connection could be established
+    pub fn connect_with_config<A: ToSocketAddrs>(addrs: A, config: CacheClientConfig) -> Result<Arc<CacheClient<KeyT, ValT>>, CacheDbError> {
+        let server_addrs = addrs.to_socket_addrs().map_err(|_| CacheDbError::NetworkError)?.map(ConnAddr::Tcp).collect();
+        CacheClient::connect_addrs(server_addrs, config)
+    }
+
+    // connects to a server listening on the unix socket path
+    #[cfg(unix)]
+    pub fn connect_unix<P: AsRef<Path>>(path: P) -> Result<Arc<CacheClient<KeyT, ValT>>, CacheDbError> {
+        CacheClient::connect_unix_with_config(path, CacheClientConfig::default())
+    }
+
+    #[cfg(unix)]
+    pub fn connect_unix_with_config<P: AsRef<Path>>(path: P, config: CacheClientConfig) -> Result<Arc<CacheClient<KeyT, ValT>>, CacheDbError> {
+        CacheClient::connect_addrs(vec![ConnAddr::Unix(Some(path.as_ref().to_path_buf()))], config)
+    }
+
+    fn connect_addrs(server_addrs: Vec<ConnAddr>, config: CacheClientConfig) -> Result<Arc<CacheClient<KeyT, ValT>>, CacheDbError> {
+        let (stream, server_hello) = CacheClient::<KeyT, ValT>::open_conn(&server_addrs, &config)?;
+        Ok(Arc::new(CacheClient {
+            conn: RwLock::new(stream),
+            server_addrs,
+            frame_mode: config.frame_mode,
+            max_frame_size: config.max_frame_size,
+            write_concern: config.write_concern,
+            ack_timeout: config.ack_timeout,
+            connect_timeout: config.connect_timeout,
+            read_timeout: config.read_timeout,
+            write_timeout: config.write_timeout,
+            request_timeout: config.request_timeout,
+            server_hello: RwLock::new(server_hello),
+            reconnect_policy: config.reconnect_policy,
+            conn_state_hook: config.conn_state_hook,
+            closed: AtomicBool::new(false),
+            connected: AtomicBool::new(true),
+            near_cache: config.near_cache.map(|near_cache_config| Mutex::new(NearCache::new(near_cache_config))),
+            watches: Mutex::new(HashMap::new()),
+            next_watch_id: AtomicU64::new(0),
+            pulls: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashMap::new()),
+            next_req_id: AtomicU32::new(1),
+
+            pd_k: PhantomData,
+            pd_v: PhantomData
+        }))
+    }
+
+    // connects to the first address that accepts the connection and exchanges the hello frames
+    // only the timeouts, the frame mode and wether there is a near cache are used
+    fn open_conn(addrs: &[ConnAddr], config: &CacheClientConfig) -> Result<(ConnStream, ProtHello), CacheDbError> {
+        let mut last_error = CacheDbError::NetworkError;
+        let capabilities = if config.near_cache.is_some() { CACHE_PROTOCOL_CAP_INVALIDATION } else { 0 };
+        for addr in addrs {
+            match ConnStream::connect(addr, config.connect_timeout) {
+                Ok(mut stream) => {
+                    stream.set_write_timeout(config.write_timeout).map_err(|e| CacheDbError::from_io_error(&e))?;
+                    let server_hello = CacheClient::<KeyT, ValT>::handshake(&mut stream, config.frame_mode, capabilities, config.read_timeout)?;
+                    return Ok((stream, server_hello));
+                }
+                Err(e) => last_error = CacheDbError::from_io_error(&e),
+            }
+        }
+        Err(last_error)
+    }
+
+    // exchanges the hello frames, before the cache_client_handler reads from the connection
+    // capabilities are requested in addition to the ones of the frame mode
+    fn handshake(stream: &mut ConnStream, frame_mode: FrameMode, capabilities: u32, read_timeout: Option<Duration>) -> Result<ProtHello, CacheDbError> {
+        let mut client_hello = ProtHello::new(frame_mode, format!("rustcachedb-client/{}", env!("CARGO_PKG_VERSION")));
+        client_hello.capabilities |= capabilities;
+        stream.write_all(&client_hello.assemble_buff(ProtOpCode::HelloOp)?).map_err(|e| CacheDbError::from_io_error(&e))?;
+
+        stream.set_read_timeout(read_timeout).map_err(|_| CacheDbError::NetworkError)?;
+        let server_hello = ProtHello::read_hello(stream, ProtOpCode::HelloReplyOp)?;
+        stream.set_read_timeout(None).map_err(|_| CacheDbError::NetworkError)?;
+
+        client_hello.check_compatible(&server_hello)?;
+        Ok(server_hello)
+    }
+
+    // a write that failed or timed out may have left a partial frame on the connection, which is closed so that the cache_client_handler stops reading from it
+    fn write_conn(&self, send_buff: &[u8]) -> Result<(), CacheDbError> {
+        let mut conn = self.conn.write().unwrap();
+        conn.write_all(send_buff).map_err(|e| {
+            let _ = conn.shutdown(Shutdown::Both);
+            CacheDbError::from_io_error(&e)
+        })
+    }
+
+    // false once the connection has been lost, until the client reconnected
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst) && !self.closed.load(Ordering::SeqCst)
+    }
+
+    // hello of the server the client is (or was last) connected to
+    pub fn server_hello(&self) -> ProtHello {
+        self.server_hello.read().unwrap().clone()
+    }
+
+    pub fn push(&self, obj: KeyValObj<KeyT, ValT>) -> Result<(), CacheDbError> {
+        self.push_with_mode(obj, PushMode::Upsert, None)
+    }
+
+    // the key expires after ttl on the server
+    pub fn push_with_ttl(&self, obj: KeyValObj<KeyT, ValT>, ttl: Duration) -> Result<(), CacheDbError> {
+        self.push_with_mode(obj, PushMode::Upsert, Some(ttl))
+    }
+
+    pub fn push_with_mode(&self, obj: KeyValObj<KeyT, ValT>, mode: PushMode, ttl: Option<Duration>) -> Result<(), CacheDbError> {
+        if self.write_concern == WriteConcern::Acknowledged {
+            return self.push_acked(obj, mode, ttl).and_then(CacheClient::<KeyT, ValT>::require_stored);
+        }
+        self.forget(&obj.key);
+        // pushes without request id are not acknowledged by the server
+        let send_buff = CacheProtocol::assemble_buff(self.frame_mode, mode.op_code(), 0, &obj, ttl)?;
+        self.write_conn(&send_buff)
+    }
+
+    // waits until the server stored the obj, returns false if it has not been stored because of the push mode
+    // fails with NetworkTimeOutError if the ack did not arrive within the ack_timeout
+    pub fn push_acked(&self, obj: KeyValObj<KeyT, ValT>, mode: PushMode, ttl: Option<Duration>) -> Result<bool, CacheDbError> {
+        self.forget(&obj.key);
+        let reply = self.request_reply(mode.op_code(), &obj, ttl, self.ack_timeout)?;
+        CacheClient::push_ack(reply)
+    }
+
+    fn push_ack(reply: ReplyFrame<KeyT, ValT>) -> Result<bool, CacheDbError> {
+        match reply.op_code {
+            ProtOpCode::PushAckOp => Ok(true),
+            ProtOpCode::PushAckNotStoredOp => Ok(false),
+            _ => Err(reply.error.unwrap_or(CacheDbError::ParsingErr)),
+        }
+    }
+
+    // for the pushes that do not return wether the obj has been stored
+    fn require_stored(stored: bool) -> Result<(), CacheDbError> {
+        match stored {
+            true => Ok(()),
+            false => Err(CacheDbError::NotStored),
+        }
+    }
+
+    // follows the write concern, unacknowledged pushes are resolved once they have been written to the connection
+    pub fn push_async(&self, obj: KeyValObj<KeyT, ValT>) -> CacheFuture<()> {
+        if self.write_concern == WriteConcern::Unacknowledged {
+            return CacheFuture::ready(self.push_with_mode(obj, PushMode::Upsert, None));
+        }
+        self.forget(&obj.key);
+        let req_id = self.next_req_id();
+        let reply = CacheProtocol::assemble_buff(self.frame_mode, ProtOpCode::PushOp, req_id, &obj, None)
+            .and_then(|send_buff| self.send_request(req_id, &send_buff));
+        CacheClient::reply_future(reply, |reply| CacheClient::push_ack(reply).and_then(CacheClient::<KeyT, ValT>::require_stored))
+    }
+
+    // pulls all keys with a single request, returns the vals in the order of the keys (None if the key could not be found)
+    pub fn pull_many(&self, keys: &[KeyT]) -> Result<Vec<Option<ValT>>, CacheDbError> {
+        let req_id = self.next_req_id();
+        let entries: Vec<(KeyValObj<KeyT, ValT>, bool)> = keys.iter().map(|key| (KeyValObj{key: key.clone(), val: ValT::default()}, false)).collect();
+        let send_buff = CacheProtocol::assemble_multi_buff(self.frame_mode, ProtOpCode::MultiPullOp, req_id, &entries, None)?;
+        let reply = self.request_reply_buff(req_id, &send_buff, self.request_timeout)?;
+        if reply.op_code != ProtOpCode::MultiPullReplyOp {
+            return Err(reply.error.unwrap_or(CacheDbError::ParsingErr));
+        }
+        if reply.entries.len() != keys.len() {
+            return Err(CacheDbError::ParsingErr);
+        }
+        Ok(reply.entries.into_iter().map(|(obj, found)| if found { Some(obj.val) } else { None }).collect())
+    }
+
+    // pushes all objs with a single request (replacing the values of existing keys), follows the write concern
+    pub fn push_many(&self, objs: &[KeyValObj<KeyT, ValT>], ttl: Option<Duration>) -> Result<(), CacheDbError> {
+        for obj in objs {
+            self.forget(&obj.key);
+        }
+        let entries: Vec<(KeyValObj<KeyT, ValT>, bool)> = objs.iter().map(|obj| (obj.clone(), true)).collect();
+        if self.write_concern == WriteConcern::Unacknowledged {
+            let send_buff = CacheProtocol::assemble_multi_buff(self.frame_mode, ProtOpCode::MultiPushOp, 0, &entries, ttl)?;
+            return self.write_conn(&send_buff);
+        }
+        let req_id = self.next_req_id();
+        let send_buff = CacheProtocol::assemble_multi_buff(self.frame_mode, ProtOpCode::MultiPushOp, req_id, &entries, ttl)?;
+        let reply = self.request_reply_buff(req_id, &send_buff, self.ack_timeout)?;
+        match reply.op_code {
+            ProtOpCode::PushAckOp => Ok(()),
+            _ => Err(reply.error.unwrap_or(CacheDbError::ParsingErr)),
+        }
+    }
+
+    // returns wether the key existed on the server
+    pub fn delete(&self, key: &KeyT) -> Result<bool, CacheDbError> {
+        self.delete_with_deadline(key, Instant::now() + self.request_timeout)
+    }
+
+    pub fn delete_with_deadline(&self, key: &KeyT, deadline: Instant) -> Result<bool, CacheDbError> {
+        self.forget(key);
+        let reply = self.request_reply(ProtOpCode::DeleteOp, &KeyValObj{key: (*key).clone(), val: ValT::default()}, None, deadline.saturating_duration_since(Instant::now()))?;
+        CacheClient::delete_reply(reply)
+    }
+
+    pub fn delete_async(&self, key: &KeyT) -> CacheFuture<bool> {
+        self.forget(key);
+        let req_id = self.next_req_id();
+        let reply = CacheProtocol::assemble_buff(self.frame_mode, ProtOpCode::DeleteOp, req_id, &KeyValObj{key: (*key).clone(), val: ValT::default()}, None)
+            .and_then(|send_buff| self.send_request(req_id, &send_buff));
+        CacheClient::reply_future(reply, CacheClient::delete_reply)
+    }
+
+    fn delete_reply(reply: ReplyFrame<KeyT, ValT>) -> Result<bool, CacheDbError> {
+        match reply.op_code {
+            ProtOpCode::DeleteReplyOp => Ok(true),
+            ProtOpCode::DeleteReplyNotFoundOp => Ok(false),
+            // the server could not handle the request
+            _ => Err(reply.error.unwrap_or(CacheDbError::ParsingErr)),
+        }
+    }
+
+    // returns the remaining ttl of the key, None if the key does not expire
+    pub fn ttl(&self, key: &KeyT) -> Result<Option<Duration>, CacheDbError> {
+        self.ttl_with_deadline(key, Instant::now() + self.request_timeout)
+    }
+
+    pub fn ttl_with_deadline(&self, key: &KeyT, deadline: Instant) -> Result<Option<Duration>, CacheDbError> {
+        let reply = self.request_reply(ProtOpCode::TtlOp, &KeyValObj{key: (*key).clone(), val: ValT::default()}, None, deadline.saturating_duration_since(Instant::now()))?;
+        match reply.op_code {
+            ProtOpCode::TtlReplyOp => Ok(reply.ttl),
+            ProtOpCode::TtlReplyNotFoundOp => Err(CacheDbError::KeyNotFound),
+            _ => Err(reply.error.unwrap_or(CacheDbError::ParsingErr)),
+        }
+    }
+
+    // 0 is never used since it is reserved for frames that are not a reply
+    fn next_req_id(&self) -> u32 {
+        loop {
+            let req_id = self.next_req_id.fetch_add(1, Ordering::Relaxed);
+            if req_id != 0 {
+                return req_id;
+            }
+        }
+    }
+
+    // sends a request and waits for its reply, which is set by the cache_client_handler
+    fn request_reply(&self, op_code: ProtOpCode, obj: &KeyValObj<KeyT, ValT>, ttl: Option<Duration>, timeout: Duration) -> Result<ReplyFrame<KeyT, ValT>, CacheDbError> {
+        let req_id = self.next_req_id();
+        let send_buff = CacheProtocol::assemble_buff(self.frame_mode, op_code, req_id, obj, ttl)?;
+        self.request_reply_buff(req_id, &send_buff, timeout)
+    }
+
+    fn request_reply_buff(&self, req_id: u32, send_buff: &[u8], timeout: Duration) -> Result<ReplyFrame<KeyT, ValT>, CacheDbError> {
+        let reply = self.send_request(req_id, send_buff)?;
+        let res = reply.wait_reply(timeout);
+        if res.is_err() {
+            self.in_flight.lock().unwrap().remove(&req_id);
+        }
+        res
+    }
+
+    // registers the request as in flight and sends it, the reply is set by the cache_client_handler
+    fn send_request(&self, req_id: u32, send_buff: &[u8]) -> Result<Arc<ReplySync<ReplyFrame<KeyT, ValT>>>, CacheDbError> {
+        let reply = Arc::new(ReplySync::new());
+        self.in_flight.lock().unwrap().insert(req_id, InFlightReq::Reply(Arc::clone(&reply)));
+        // checked after the request is in flight, so that it is either failed here or by the cache_client_handler
+        if self.closed.load(Ordering::SeqCst) {
+            self.in_flight.lock().unwrap().remove(&req_id);
+            return Err(CacheDbError::NetworkError);
+        }
+        if let Err(e) = self.write_conn(send_buff) {
+            self.in_flight.lock().unwrap().remove(&req_id);
+            return Err(e);
+        }
+        Ok(reply)
+    }
+
+    // resolves to the reply mapped by map_reply
+    fn reply_future<T: Send + 'static>(reply: Result<Arc<ReplySync<ReplyFrame<KeyT, ValT>>>, CacheDbError>, map_reply: fn(ReplyFrame<KeyT, ValT>) -> Result<T, CacheDbError>) -> CacheFuture<T> {
+        match reply {
+            Ok(reply) => {
+                let mut waker_index = None;
+                CacheFuture { poll_fn: Box::new(move |cx| reply.poll_reply(cx, &mut waker_index).map(map_reply)) }
+            }
+            Err(e) => CacheFuture::ready(Err(e)),
+        }
+    }
+
+    // registers the pull as in flight and sends it
+    fn send_pull(&self, req_id: u32, key: &KeyT) -> Result<(), CacheDbError> {
+        let send_buff = CacheProtocol::assemble_buff(self.frame_mode, ProtOpCode::PullOp, req_id, &KeyValObj{key: (*key).clone(), val: ValT::default()}, None)?;
+        self.in_flight.lock().unwrap().insert(req_id, InFlightReq::Pull((*key).clone()));
+        // checked after the pull is in flight, so that it is either failed here or by the cache_client_handler
+        if self.closed.load(Ordering::SeqCst) {
+            self.in_flight.lock().unwrap().remove(&req_id);
+            return Err(CacheDbError::NetworkError);
+        }
+        if let Err(e) = self.write_conn(&send_buff) {
+            // the pull stays in flight and is re-issued once the cache_client_handler reconnected
+            if self.reconnect_policy.is_some() && !self.closed.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            self.in_flight.lock().unwrap().remove(&req_id);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // returns the pending pull of the key, the pull is only sent if the key is not being pulled already
+    // the request id is returned to the caller that sent the pull
+    fn join_pull(&self, key: &KeyT) -> Result<(Arc<PullReply<ValT>>, Option<u32>), CacheDbError> {
+        let (req_id, reply) = {
+            let mut pulls = self.pulls.lock().unwrap();
+            if let Some(pending) = pulls.get(key) {
+                return Ok((Arc::clone(&pending.reply), None));
+            }
+            let req_id = self.next_req_id();
+            let reply = Arc::new(ReplySync::new());
+            pulls.insert(key.clone(), PendingPull{req_id, reply: Arc::clone(&reply), invalidated: false});
+            (req_id, reply)
+        };
+        // the pulls are not locked while writing, so that the cache_client_handler is never blocked by a write
+        if let Err(e) = self.send_pull(req_id, key) {
+            self.resolve_pull(req_id, key, Err(e.clone()));
+            return Err(e);
+        }
+        Ok((reply, Some(req_id)))
+    }
+
+    // number of keys that are being pulled
+    pub fn pulls_in_flight(&self) -> usize {
+        self.pulls.lock().unwrap().len()
+    }
+
+    // None if the client has no near cache
+    pub fn near_cache_stats(&self) -> Option<NearCacheStats> {
+        self.near_cache.as_ref().map(|near_cache| near_cache.lock().unwrap().stats())
+    }
+
+    fn near_cache_get(&self, key: &KeyT) -> Option<ValT> {
+        self.near_cache.as_ref()?.lock().unwrap().get(key, Instant::now())
+    }
+
+    // drops the local copy of a key that is written by this client, so that the client reads its own writes
+    fn forget(&self, key: &KeyT) {
+        if let Some(near_cache) = &self.near_cache {
+            near_cache.lock().unwrap().remove(key);
+        }
+    }
+
+    // the key changed on the server, a pull that is in flight might have read the previous val
+    fn invalidate(&self, key: &KeyT) {
+        if let Some(near_cache) = &self.near_cache {
+            if let Some(pending) = self.pulls.lock().unwrap().get_mut(key) {
+                pending.invalidated = true;
+            }
+            let mut near_cache = near_cache.lock().unwrap();
+            if near_cache.remove(key) {
+                near_cache.stats.invalidations += 1;
+            }
+        }
+    }
+
+    pub fn pull(&self, key: &KeyT, res: &mut KeyValObj<KeyT, ValT>) -> Result<(), CacheDbError> {
+        self.pull_with_deadline(key, res, Instant::now() + self.request_timeout)
+    }
+
+    // fails with NetworkTimeOutError if the reply did not arrive before the deadline
+    // if the pull sent by this call times out, the pullers waiting for it fail as well instead of waiting for a reply that might never arrive
+    pub fn pull_with_deadline(&self, key: &KeyT, res: &mut KeyValObj<KeyT, ValT>, deadline: Instant) -> Result<(), CacheDbError> {
+        if let Some(val) = self.near_cache_get(key) {
+            *res = KeyValObj{key: key.clone(), val};
+            return Ok(());
+        }
+        // if the key is already being pulled by somebody else we wait for that reply instead of sending our own pull
+        let (reply, sent_req_id) = self.join_pull(key)?;
+        match reply.wait_shared_reply(deadline.saturating_duration_since(Instant::now())) {
+            Ok(pulled) => {
+                *res = KeyValObj{key: key.clone(), val: pulled?};
+                Ok(())
+            }
+            Err(e) => {
+                // the reply is being resolved by the cache_client_handler if it is no longer in flight
+                if let Some(req_id) = sent_req_id {
+                    if self.in_flight.lock().unwrap().remove(&req_id).is_some() {
+                        self.resolve_pull(req_id, key, Err(e.clone()));
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    // like pull, the future waits for a pull of the key that has already been sent by somebody else instead of sending its own
+    pub fn pull_async(&self, key: &KeyT) -> CacheFuture<KeyValObj<KeyT, ValT>> {
+        if let Some(val) = self.near_cache_get(key) {
+            return CacheFuture::ready(Ok(KeyValObj{key: key.clone(), val}));
+        }
+        let reply = match self.join_pull(key) {
+            Ok((reply, _)) => reply,
+            Err(e) => return CacheFuture::ready(Err(e)),
+        };
+        let key = key.clone();
+        let mut waker_index = None;
+        CacheFuture { poll_fn: Box::new(move |cx| reply.poll_shared_reply(cx, &mut waker_index).map(|pulled| pulled.map(|val| KeyValObj{key: key.clone(), val}))) }
+    }
+
+    // the receiver is disconnected once the target is unwatched or the connection is lost
+    pub fn watch(&self, target: WatchTarget<KeyT>) -> Result<mpsc::Receiver<WatchEvent<KeyT, ValT>>, CacheDbError> {
+        let (sender, receiver) = mpsc::channel();
+        self.add_watch(target, WatchSink::Channel(sender))?;
+        Ok(receiver)
+    }
+
+    // the callback is called until the target is unwatched or the connection is lost
+    pub fn watch_with_callback(&self, target: WatchTarget<KeyT>, callback: WatchCallback<KeyT, ValT>) -> Result<(), CacheDbError> {
+        self.add_watch(target, WatchSink::Callback(callback))
+    }
+
+    // the watch is added before the WatchOp is sent, so that no change after the ack is missed
+    // only the added watch is removed if the server did not acknowledge it, other watches of the target are kept
+    fn add_watch(&self, target: WatchTarget<KeyT>, sink: WatchSink<KeyT, ValT>) -> Result<(), CacheDbError> {
+        let watch_id = self.next_watch_id.fetch_add(1, Ordering::Relaxed);
+        self.watches.lock().unwrap().entry(target.clone()).or_default().push((watch_id, sink));
+        let op_code = match target {
+            WatchTarget::Key(_) => ProtOpCode::WatchOp,
+            WatchTarget::Prefix(_) => ProtOpCode::WatchPrefixOp,
+        };
+        let res = self.request_watch(op_code, &target);
+        if res.is_err() {
+            let mut watches = self.watches.lock().unwrap();
+            if let Some(sinks) = watches.get_mut(&target) {
+                sinks.retain(|(id, _)| *id != watch_id);
+                if sinks.is_empty() {
+                    watches.remove(&target);
+                }
+            }
+        }
+        res
+    }
+
+    // removes all watches of the target
+    pub fn unwatch(&self, target: &WatchTarget<KeyT>) -> Result<(), CacheDbError> {
+        self.watches.lock().unwrap().remove(target);
+        let op_code = match target {
+            WatchTarget::Key(_) => ProtOpCode::UnwatchOp,
+            WatchTarget::Prefix(_) => ProtOpCode::UnwatchPrefixOp,
+        };
+        self.request_watch(op_code, target)
+    }
+
+    fn request_watch(&self, op_code: ProtOpCode, target: &WatchTarget<KeyT>) -> Result<(), CacheDbError> {
+        let key = match target {
+            WatchTarget::Key(key) | WatchTarget::Prefix(key) => key.clone(),
+        };
+        let reply = self.request_reply(op_code, &KeyValObj{key, val: ValT::default()}, None, self.request_timeout)?;
+        match reply.op_code {
+            ProtOpCode::WatchAckOp => Ok(()),
+            _ => Err(reply.error.unwrap_or(CacheDbError::ParsingErr)),
+        }
+    }
+
+    // passes the event to all watches of its key, watches whose receiver has been dropped are removed
+    // the callbacks are called after the watches have been unlocked, so that they can add watches themselves
+    fn deliver(&self, event: WatchEvent<KeyT, ValT>) {
+        let mut callbacks = Vec::new();
+        {
+            let mut watches = self.watches.lock().unwrap();
+            if watches.is_empty() {
+                return;
+            }
+            let (WatchEvent::Changed(key, _) | WatchEvent::Deleted(key)) = &event;
+            let key_bytes = key.get_bytes();
+            for (target, sinks) in watches.iter_mut() {
+                let matches = match target {
+                    WatchTarget::Key(watched_key) => watched_key == key,
+                    WatchTarget::Prefix(prefix) => key_bytes.starts_with(&prefix.get_bytes()),
+                };
+                if !matches {
+                    continue;
+                }
+                sinks.retain(|(_, sink)| match sink {
+                    WatchSink::Channel(sender) => sender.send(event.clone()).is_ok(),
+                    WatchSink::Callback(callback) => {
+                        callbacks.push(Arc::clone(callback));
+                        true
+                    }
+                });
+            }
+            watches.retain(|_, sinks| !sinks.is_empty());
+        }
+        for callback in callbacks {
+            callback(&event);
+        }
+    }
+
+    // closes the connection without TerminateConn, the cache_client_handler returns and does not reconnect
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        let _ = self.conn.read().unwrap().shutdown(Shutdown::Both);
+    }
+
+    pub fn terminate_conn(&mut self) -> io::Result<usize> {
+        // key/val size 0/ 0
+        let term_seq = match CacheProtocol::assemble_buff(self.frame_mode, ProtOpCode::TerminateConn, self.next_req_id(), &KeyValObj{key: KeyT::default(), val: ValT::default()}, None) {
+            Ok(term_seq) => term_seq,
+            Err(_) => return Err(io::Error::from(io::ErrorKind::InvalidData)),
+        };
+        // the cache_client_handler must not reconnect
+        self.closed.store(true, Ordering::SeqCst);
+        self.conn.write().unwrap().write_all(&term_seq)?;
+        Ok(term_seq.len())
+    }
+
+    // wakes up all pullers of the key and removes the pending pull, so that the next pull of the key is sent again
+    // pulled vals are added to the near cache while the pull is still pending, so that an InvalidateOp can not be missed
+    fn resolve_pull(&self, req_id: u32, key: &KeyT, res: Result<ValT, CacheDbError>) {
+        let mut pulls = self.pulls.lock().unwrap();
+        if pulls.get(key).is_some_and(|pending| pending.req_id == req_id) {
+            if let (Some(near_cache), Ok(val), Some(false)) = (&self.near_cache, &res, pulls.get(key).map(|pending| pending.invalidated)) {
+                near_cache.lock().unwrap().insert(key.clone(), val.clone(), Instant::now());
+            }
+            if let Some(pending) = pulls.remove(key) {
+                drop(pulls);
+                pending.reply.set_reply(res);
+            }
+        }
+    }
+
+    // fails all requests that are in flight, since they will not be replied to anymore
+    fn fail_in_flight(&self, error: &CacheDbError) {
+        let in_flight: Vec<(u32, InFlightReq<KeyT, ValT>)> = self.in_flight.lock().unwrap().drain().collect();
+        for (req_id, in_flight_req) in in_flight {
+            match in_flight_req {
+                InFlightReq::Pull(key) => self.resolve_pull(req_id, &key, Err(error.clone())),
+                InFlightReq::Reply(reply) => reply.set_reply(ReplyFrame::from_error(error.clone())),
+            }
+        }
+    }
+
+    // reads replies until the connection is terminated by the server
+    fn read_replies(&self) -> Result<(), CacheDbError> {
+        let mut buff = [0; TCP_READ_BUFF_SIZE];
+
+        let mut parser = CacheProtocol::<KeyT, ValT>::with_frame_mode(self.frame_mode, self.max_frame_size);
+        let mut parsed_op_code: ProtOpCode = ProtOpCode::PullOp;
+        let mut parsed_obj: KeyValObj<KeyT, ValT> = KeyValObj {
+            key: KeyT::default(),
+            val: ValT::default(),
+        };
+        let mut cloned_socket = self.conn.write().unwrap().try_clone().map_err(|_| CacheDbError::NetworkError)?;
+        cloned_socket.set_read_timeout(self.read_timeout).map_err(|_| CacheDbError::NetworkError)?;
+        // set once a read timed out while requests were in flight, the connection stalled if the next one times out as well
+        let mut stalled = false;
+        loop {
+            let tcp_read_size = match cloned_socket.read(&mut buff) {
+                Err(e) if CacheDbError::from_io_error(&e) == CacheDbError::NetworkTimeOutError => {
+                    if self.in_flight.lock().unwrap().is_empty() {
+                        stalled = false;
+                    } else if stalled {
+                        return Err(CacheDbError::NetworkTimeOutError);
+                    } else {
+                        stalled = true;
+                    }
+                    continue;
+                }
+                Err(_) => return Err(CacheDbError::NetworkError),
+                Ok(size) => size
+            };
+            stalled = false;
+            // the connection has been closed by the server without TerminateConn
+            if tcp_read_size == 0 {
+                return Err(CacheDbError::NetworkError);
+            }
+            parser.feed(&buff[..tcp_read_size]);
+
+            // parsing all frames that have been received completely
+            while parser.parse_buff(&mut parsed_op_code, &mut parsed_obj)? {
+                // successfully parsed parsed_obj is now updated to latest parsed obj (such as parsed_op_code)
+                match parsed_op_code {
+                    ProtOpCode::TerminateConn => {
+                        return Ok(());
+                    },
+                    // error replies without request are sent before the server closes the connection
+                    ProtOpCode::ErrorReplyOp if parser.parsed_req_id() == 0 => {
+                        return Err(parser.parsed_error().unwrap_or(CacheDbError::ParsingErr));
+                    }
+                    ProtOpCode::InvalidateOp => {
+                        self.invalidate(&parsed_obj.key);
+                    }
+                    ProtOpCode::ChangeNotifyOp => {
+                        self.deliver(WatchEvent::Changed(parsed_obj.key.clone(), parsed_obj.val.clone()));
+                    }
+                    ProtOpCode::ChangeNotifyDeletedOp => {
+                        self.deliver(WatchEvent::Deleted(parsed_obj.key.clone()));
+                    }
+                    ProtOpCode::PullReplyOp | ProtOpCode::PullReplyNotFoundOp | ProtOpCode::DeleteReplyOp | ProtOpCode::DeleteReplyNotFoundOp |
+                    ProtOpCode::TtlReplyOp | ProtOpCode::TtlReplyNotFoundOp | ProtOpCode::PushAckOp | ProtOpCode::PushAckNotStoredOp | ProtOpCode::MultiPullReplyOp |
+                    ProtOpCode::WatchAckOp | ProtOpCode::ErrorReplyOp => {
+                        // replies to requests that already timed out are no longer in flight and dropped
+                        let in_flight_req = self.in_flight.lock().unwrap().remove(&parser.parsed_req_id());
+                        match in_flight_req {
+                            Some(InFlightReq::Pull(key)) => {
+                                let res = match parsed_op_code {
+                                    ProtOpCode::PullReplyOp => Ok(parsed_obj.val.clone()),
+                                    ProtOpCode::PullReplyNotFoundOp => Err(CacheDbError::KeyNotFound),
+                                    _ => Err(parser.parsed_error().unwrap_or(CacheDbError::ParsingErr)),
+                                };
+                                self.resolve_pull(parser.parsed_req_id(), &key, res);
+                            }
+                            Some(InFlightReq::Reply(reply)) => {
+                                reply.set_reply(ReplyFrame{op_code: parsed_op_code, ttl: parser.parsed_ttl(), error: parser.parsed_error(), entries: parser.take_parsed_entries()});
+                            }
+                            None => {}
+                        }
+                    },
+                    _ => {
+                        return Err(CacheDbError::ParsingErr);
+                    }
+                }
+            }
+        }
+    }
+
+    // InvalidateOps and ChangeNotifyOps are missed while the connection is lost, the receivers of the watches are disconnected
+    fn drop_conn_state(&self) {
+        if let Some(near_cache) = &self.near_cache {
+            near_cache.lock().unwrap().clear();
+        }
+        self.watches.lock().unwrap().clear();
+    }
+
+    fn emit_conn_state(&self, state: ConnState) {
+        if let Some(hook) = &self.conn_state_hook {
+            hook(&state);
+        }
+    }
+
+    // connects to the server again until the retry budget of the reconnect policy is exhausted
+    // pulls that are still in flight are re-issued on the new connection, all other requests fail since they might have been handled already
+    fn reconnect(&self, policy: &ReconnectPolicy) -> Result<(), CacheDbError> {
+        let in_flight_replies: Vec<u32> = self.in_flight.lock().unwrap().iter()
+            .filter(|(_, in_flight_req)| matches!(in_flight_req, InFlightReq::Reply(_)))
+            .map(|(req_id, _)| *req_id)
+            .collect();
+        for req_id in in_flight_replies {
+            if let Some(InFlightReq::Reply(reply)) = self.in_flight.lock().unwrap().remove(&req_id) {
+                reply.set_reply(ReplyFrame::from_error(CacheDbError::NetworkError));
+            }
+        }
+
+        let mut last_error = CacheDbError::NetworkError;
+        for attempt in 1..=policy.max_retries {
+            let rand = (RandomState::new().hash_one(attempt) >> 11) as f64 / (1u64 << 53) as f64;
+            let backoff = policy.backoff(attempt, rand);
+            self.emit_conn_state(ConnState::Reconnecting{attempt, backoff});
+            thread::sleep(backoff);
+            if self.closed.load(Ordering::SeqCst) {
+                return Err(last_error);
+            }
+
+            let conn_config = CacheClientConfig{
+                frame_mode: self.frame_mode,
+                connect_timeout: self.connect_timeout,
+                read_timeout: self.read_timeout,
+                write_timeout: self.write_timeout,
+                near_cache: self.near_cache.as_ref().map(|near_cache| near_cache.lock().unwrap().config),
+                ..Default::default()
+            };
+            let (stream, server_hello) = match CacheClient::<KeyT, ValT>::open_conn(&self.server_addrs, &conn_config) {
+                Ok(conn) => conn,
+                Err(e) => {
+                    last_error = e;
+                    continue;
+                }
+            };
+
+            // pulls that are sent from now on use the new connection, the ones that are in flight already are re-issued while it is locked
+            let mut conn = self.conn.write().unwrap();
+            *conn = stream;
+            *self.server_hello.write().unwrap() = server_hello;
+            let in_flight_pulls: Vec<(u32, KeyT)> = self.in_flight.lock().unwrap().iter()
+                .filter_map(|(req_id, in_flight_req)| match in_flight_req {
+                    InFlightReq::Pull(key) => Some((*req_id, key.clone())),
+                    InFlightReq::Reply(_) => None,
+                })
+                .collect();
+            let mut send_res = Ok(());
+            for (req_id, key) in in_flight_pulls {
+                let send_buff = CacheProtocol::assemble_buff(self.frame_mode, ProtOpCode::PullOp, req_id, &KeyValObj{key, val: ValT::default()}, None)?;
+                send_res = conn.write_all(&send_buff);
+                if send_res.is_err() {
+                    break;
+                }
+            }
+            drop(conn);
+            if send_res.is_err() {
+                last_error = CacheDbError::NetworkError;
+                continue;
+            }
+            self.emit_conn_state(ConnState::Reconnected);
+            return Ok(());
+        }
+        Err(last_error)
+    }
+
+    // reads replies until the connection is terminated, reconnects if the client has a reconnect policy
+    // returns an error only if the connection has been lost and could not be reestablished
+    pub fn cache_client_handler(cache_client: &Arc<CacheClient<KeyT, ValT>>) -> JoinHandle<Result<(), CacheDbError>> {
+        let ccache_clone = Arc::clone(cache_client);
+        thread::spawn(move || {
+            let mut res = ccache_clone.read_replies();
+            ccache_clone.connected.store(false, Ordering::SeqCst);
+            ccache_clone.drop_conn_state();
+            if let Some(policy) = ccache_clone.reconnect_policy.clone() {
+                while !ccache_clone.closed.load(Ordering::SeqCst) {
+                    ccache_clone.emit_conn_state(ConnState::Disconnected(res.clone().err().unwrap_or(CacheDbError::NetworkError)));
+                    if let Err(e) = ccache_clone.reconnect(&policy) {
+                        ccache_clone.closed.store(true, Ordering::SeqCst);
+                        ccache_clone.emit_conn_state(ConnState::Failed(e.clone()));
+                        res = Err(e);
+                        break;
+                    }
+                    ccache_clone.connected.store(true, Ordering::SeqCst);
+                    res = ccache_clone.read_replies();
+                    ccache_clone.connected.store(false, Ordering::SeqCst);
+                    ccache_clone.drop_conn_state();
+                }
+            }
+            ccache_clone.closed.store(true, Ordering::SeqCst);
+            ccache_clone.fail_in_flight(res.as_ref().err().unwrap_or(&CacheDbError::NetworkError));
+            res
+        })
+    }
+}
+
+impl<KeyT: 'static, ValT: 'static> CacheClientPool<KeyT, ValT> where KeyT: GenericKeyVal<KeyT> + Hash + Eq + Clone + Default + Debug + Send + Sync, ValT: GenericKeyVal<ValT> + Clone + Debug + Default + Send + Sync {
+
+    pub fn create_connect(ipv4_addr: [u8; 4], port: u16, size: usize) -> Result<CacheClientPool<KeyT, ValT>, CacheDbError> {
+        CacheClientPool::create_connect_with_config(ipv4_addr, port, CacheClientPoolConfig{size, ..Default::default()})
+    }
+
+    pub fn create_connect_with_config(ipv4_addr: [u8; 4], port: u16, config: CacheClientPoolConfig) -> Result<CacheClientPool<KeyT, ValT>, CacheDbError> {
+        CacheClientPool::connect_with_config(SocketAddr::from((ipv4_addr, port)), config)
+    }
+
+    // connects all clients and starts their cache_client_handler, fails if any of them can not connect
+    // addrs are resolved once and shared by all clients
+    pub fn connect_with_config<A: ToSocketAddrs>(addrs: A, config: CacheClientPoolConfig) -> Result<CacheClientPool<KeyT, ValT>, CacheDbError> {
+        let addrs = addrs.to_socket_addrs().map_err(|_| CacheDbError::NetworkError)?.map(ConnAddr::Tcp).collect();
+        CacheClientPool::connect_addrs(addrs, config)
+    }
+
+    #[cfg(unix)]
+    pub fn connect_unix_with_config<P: AsRef<Path>>(path: P, config: CacheClientPoolConfig) -> Result<CacheClientPool<KeyT, ValT>, CacheDbError> {
+        CacheClientPool::connect_addrs(vec![ConnAddr::Unix(Some(path.as_ref().to_path_buf()))], config)
+    }
+
+    fn connect_addrs(addrs: Vec<ConnAddr>, config: CacheClientPoolConfig) -> Result<CacheClientPool<KeyT, ValT>, CacheDbError> {
+        let mut clients = Vec::with_capacity(config.size.max(1));
+        for _ in 0..config.size.max(1) {
+            clients.push(CacheClient::connect_addrs(addrs.clone(), config.client_config.clone())?);
+        }
+        let client_handlers = clients.iter().map(CacheClient::cache_client_handler).collect();
+        Ok(CacheClientPool {
+            clients,
+            client_handlers,
+            dispatch: config.dispatch,
+            next_client: AtomicUsize::new(0),
+            key_hasher: RandomState::new(),
+            pulls: Mutex::new(HashMap::new()),
+            deduplicated_pulls: AtomicU64::new(0),
+            request_timeout: config.client_config.request_timeout,
+        })
+    }
+
+    pub fn stats(&self) -> CacheClientPoolStats {
+        CacheClientPoolStats {
+            size: self.clients.len(),
+            connected: self.clients.iter().filter(|client| client.is_connected()).count(),
+            pulls_in_flight: self.pulls.lock().unwrap().len(),
+            deduplicated_pulls: self.deduplicated_pulls.load(Ordering::Relaxed),
+        }
+    }
+
+    // connected clients are preferred by the round robin dispatch
+    fn next_client(&self) -> &Arc<CacheClient<KeyT, ValT>> {
+        let start = self.next_client.fetch_add(1, Ordering::Relaxed);
+        for i in 0..self.clients.len() {
+            let client = &self.clients[(start + i) % self.clients.len()];
+            if client.is_connected() {
+                return client;
+            }
+        }
+        &self.clients[start % self.clients.len()]
+    }
+
+    fn client(&self, key: &KeyT) -> &Arc<CacheClient<KeyT, ValT>> {
+        match self.dispatch {
+            PoolDispatch::RoundRobin => self.next_client(),
+            PoolDispatch::KeyHash => &self.clients[(self.key_hasher.hash_one(key) % self.clients.len() as u64) as usize],
+        }
+    }
+
+    pub fn push(&self, obj: KeyValObj<KeyT, ValT>) -> Result<(), CacheDbError> {
+        self.client(&obj.key).push(obj)
+    }
+
+    pub fn push_with_ttl(&self, obj: KeyValObj<KeyT, ValT>, ttl: Duration) -> Result<(), CacheDbError> {
+        self.client(&obj.key).push_with_ttl(obj, ttl)
+    }
+
+    pub fn push_with_mode(&self, obj: KeyValObj<KeyT, ValT>, mode: PushMode, ttl: Option<Duration>) -> Result<(), CacheDbError> {
+        self.client(&obj.key).push_with_mode(obj, mode, ttl)
+    }
+
+    pub fn push_acked(&self, obj: KeyValObj<KeyT, ValT>, mode: PushMode, ttl: Option<Duration>) -> Result<bool, CacheDbError> {
+        self.client(&obj.key).push_acked(obj, mode, ttl)
+    }
+
+    pub fn pull_many(&self, keys: &[KeyT]) -> Result<Vec<Option<ValT>>, CacheDbError> {
+        self.next_client().pull_many(keys)
+    }
+
+    pub fn push_many(&self, objs: &[KeyValObj<KeyT, ValT>], ttl: Option<Duration>) -> Result<(), CacheDbError> {
+        self.next_client().push_many(objs, ttl)
+    }
+
+    pub fn delete(&self, key: &KeyT) -> Result<bool, CacheDbError> {
+        self.client(key).delete(key)
+    }
+
+    pub fn ttl(&self, key: &KeyT) -> Result<Option<Duration>, CacheDbError> {
+        self.client(key).ttl(key)
+    }
+
+    // only the first caller pulls the key, all others wait for its reply
+    pub fn pull(&self, key: &KeyT, res: &mut KeyValObj<KeyT, ValT>) -> Result<(), CacheDbError> {
+        let (pull, is_puller) = {
+            let mut pulls = self.pulls.lock().unwrap();
+            match pulls.get(key) {
+                Some(pull) => (Arc::clone(pull), false),
+                None => {
+                    let pull = Arc::new(ReplySync::new());
+                    pulls.insert(key.clone(), Arc::clone(&pull));
+                    (pull, true)
+                }
+            }
+        };
+        if is_puller {
+            let mut pulled = KeyValObj{key: key.clone(), val: ValT::default()};
+            let pull_res = self.client(key).pull(key, &mut pulled).map(|_| pulled.val);
+            self.pulls.lock().unwrap().remove(key);
+            pull.set_reply(pull_res);
+        } else {
+            self.deduplicated_pulls.fetch_add(1, Ordering::Relaxed);
+        }
+        let val = pull.wait_shared_reply(self.request_timeout)??;
+        *res = KeyValObj{key: key.clone(), val};
+        Ok(())
+    }
+
+    // waits for the cache_client_handlers, which return once the server terminated the connections
+    pub fn join(self) -> Vec<Result<(), CacheDbError>> {
+        self.client_handlers.into_iter().map(|client_handler| client_handler.join().unwrap_or(Err(CacheDbError::NetworkError))).collect()
+    }
+}
+
+impl<KeyT: 'static, ValT: 'static> CacheClusterClient<KeyT, ValT> where KeyT: GenericKeyVal<KeyT> + Hash + Eq + Clone + Default + Debug + Send + Sync, ValT: GenericKeyVal<ValT> + Clone + Debug + Default + Send + Sync {
+
+    pub fn create_connect(nodes: &[([u8; 4], u16)]) -> Result<CacheClusterClient<KeyT, ValT>, CacheDbError> {
+        CacheClusterClient::create_connect_with_config(nodes, CacheClusterConfig::default())
+    }
+
+    // connects to all nodes, fails if any of them can not be connected to
+    pub fn create_connect_with_config(nodes: &[([u8; 4], u16)], config: CacheClusterConfig) -> Result<CacheClusterClient<KeyT, ValT>, CacheDbError> {
+        let cluster_client = CacheClusterClient {
+            nodes: RwLock::new(ClusterNodes{ring: HashRing::new(config.virtual_nodes), clients: HashMap::new()}),
+            client_config: config.client_config,
+        };
+        for (ipv4_addr, port) in nodes {
+            cluster_client.add_node(*ipv4_addr, *port)?;
+        }
+        Ok(cluster_client)
+    }
+
+    // only the keys that belong to the points of the new node are routed to it from now on
+    pub fn add_node(&self, ipv4_addr: [u8; 4], port: u16) -> Result<(), CacheDbError> {
+        self.add_node_addr(SocketAddr::from((ipv4_addr, port)))
+    }
+
+    // the address identifies the node on the hash ring
+    pub fn add_node_addr(&self, addr: SocketAddr) -> Result<(), CacheDbError> {
+        if self.nodes.read().unwrap().clients.contains_key(&addr) {
+            return Ok(());
+        }
+        let client = CacheClient::connect_with_config(addr, self.client_config.clone())?;
+        let client_handler = CacheClient::cache_client_handler(&client);
+        let mut nodes = self.nodes.write().unwrap();
+        // the node has been added by another caller while connecting, the connection of the first caller is kept
+        if nodes.clients.contains_key(&addr) {
+            drop(nodes);
+            client.close();
+            let _ = client_handler.join();
+            return Ok(());
+        }
+        nodes.ring.add(addr);
+        nodes.clients.insert(addr, ClusterNode{client, client_handler});
+        Ok(())
+    }
+
+    // the keys of the node are routed to the remaining nodes, returns false if it is not part of the cluster
+    pub fn remove_node(&self, ipv4_addr: [u8; 4], port: u16) -> bool {
+        self.remove_node_addr(SocketAddr::from((ipv4_addr, port)))
+    }
+
+    pub fn remove_node_addr(&self, addr: SocketAddr) -> bool {
+        let node = {
+            let mut nodes = self.nodes.write().unwrap();
+            nodes.ring.remove(&addr);
+            nodes.clients.remove(&addr)
+        };
+        match node {
+            Some(node) => {
+                node.client.close();
+                let _ = node.client_handler.join();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn nodes(&self) -> Vec<SocketAddr> {
+        self.nodes.read().unwrap().clients.keys().copied().collect()
+    }
+
+    // fails with NetworkError if the cluster has no nodes
+    fn client(&self, key: &KeyT) -> Result<Arc<CacheClient<KeyT, ValT>>, CacheDbError> {
+        let nodes = self.nodes.read().unwrap();
+        nodes.ring.node(&key.get_bytes()).and_then(|node| nodes.clients.get(&node).map(|node| Arc::clone(&node.client))).ok_or(CacheDbError::NetworkError)
+    }
+
+    // groups the indices of the keys by the node they belong to
+    fn group_by_client<'a>(&self, keys: impl Iterator<Item = &'a KeyT>) -> Result<Vec<NodeGroup<KeyT, ValT>>, CacheDbError> {
+        let nodes = self.nodes.read().unwrap();
+        let mut groups: HashMap<SocketAddr, Vec<usize>> = HashMap::new();
+        for (i, key) in keys.enumerate() {
+            let node = nodes.ring.node(&key.get_bytes()).ok_or(CacheDbError::NetworkError)?;
+            groups.entry(node).or_default().push(i);
+        }
+        groups.into_iter().map(|(node, indices)| nodes.clients.get(&node).map(|node| (Arc::clone(&node.client), indices)).ok_or(CacheDbError::NetworkError)).collect()
+    }
+
+    pub fn push(&self, obj: KeyValObj<KeyT, ValT>) -> Result<(), CacheDbError> {
+        self.client(&obj.key)?.push(obj)
+    }
+
+    pub fn push_with_ttl(&self, obj: KeyValObj<KeyT, ValT>, ttl: Duration) -> Result<(), CacheDbError> {
+        self.client(&obj.key)?.push_with_ttl(obj, ttl)
+    }
+
+    pub fn push_with_mode(&self, obj: KeyValObj<KeyT, ValT>, mode: PushMode, ttl: Option<Duration>) -> Result<(), CacheDbError> {
+        self.client(&obj.key)?.push_with_mode(obj, mode, ttl)
+    }
+
+    pub fn push_acked(&self, obj: KeyValObj<KeyT, ValT>, mode: PushMode, ttl: Option<Duration>) -> Result<bool, CacheDbError> {
+        self.client(&obj.key)?.push_acked(obj, mode, ttl)
+    }
+
+    pub fn pull(&self, key: &KeyT, res: &mut KeyValObj<KeyT, ValT>) -> Result<(), CacheDbError> {
+        self.client(key)?.pull(key, res)
+    }
+
+    pub fn delete(&self, key: &KeyT) -> Result<bool, CacheDbError> {
+        self.client(key)?.delete(key)
+    }
+
+    pub fn ttl(&self, key: &KeyT) -> Result<Option<Duration>, CacheDbError> {
+        self.client(key)?.ttl(key)
+    }
+
+    // sends one batch per node, the vals are returned in the order of the keys
+    pub fn pull_many(&self, keys: &[KeyT]) -> Result<Vec<Option<ValT>>, CacheDbError> {
+        let mut vals = vec![None; keys.len()];
+        for (client, indices) in self.group_by_client(keys.iter())? {
+            let node_keys: Vec<KeyT> = indices.iter().map(|i| keys[*i].clone()).collect();
+            for (i, val) in indices.into_iter().zip(client.pull_many(&node_keys)?) {
+                vals[i] = val;
+            }
+        }
+        Ok(vals)
+    }
+
+    // sends one batch per node
+    pub fn push_many(&self, objs: &[KeyValObj<KeyT, ValT>], ttl: Option<Duration>) -> Result<(), CacheDbError> {
+        for (client, indices) in self.group_by_client(objs.iter().map(|obj| &obj.key))? {
+            let node_objs: Vec<KeyValObj<KeyT, ValT>> = indices.into_iter().map(|i| objs[i].clone()).collect();
+            client.push_many(&node_objs, ttl)?;
+        }
+        Ok(())
+    }
+}
+
+impl<KeyT: 'static, ValT: 'static> CacheDb<KeyT, ValT> where KeyT: Hash + Eq + GenericKeyVal<KeyT> + Default + Debug + Send + Sync + Clone, ValT: GenericKeyVal<ValT> + Default + Debug + Send + Sync, KeyValObj<KeyT, ValT>: Clone {
+    pub fn new(ipv4_addr: [u8; 4], port: u16) -> Arc<CacheDb<KeyT, ValT>> {
+        CacheDb::new_with_config(ipv4_addr, port, CacheDbConfig::default())
+    }
+
+    pub fn new_with_config(ipv4_addr: [u8; 4], port: u16, config: CacheDbConfig<KeyT>) -> Arc<CacheDb<KeyT, ValT>> {
+        CacheDb::with_addrs(vec![SocketAddr::from((ipv4_addr, port))], config)
+    }
+
+    // the server listens on every address addrs resolves to (such as "[::]:7000" or a list of SocketAddr)
+    // fails if addrs can not be resolved or resolves to no address, unless the server listens on a unix socket
+    pub fn new_with_addrs<A: ToSocketAddrs>(addrs: A, config: CacheDbConfig<KeyT>) -> io::Result<Arc<CacheDb<KeyT, ValT>>> {
+        let addrs: Vec<SocketAddr> = addrs.to_socket_addrs()?.collect();
+        if addrs.is_empty() && config.unix_socket_path.is_none() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no address to listen on"));
+        }
+        Ok(CacheDb::with_addrs(addrs, config))
+    }
+
+    fn with_addrs(addrs: Vec<SocketAddr>, config: CacheDbConfig<KeyT>) -> Arc<CacheDb<KeyT, ValT>> {
+        let usage = Arc::new(StoreUsage::default());
+        let shards = (0..config.shard_count.max(1)).map(|_| Shard {
+            key_val_store: RwLock::new(KeyValStore::new(Arc::clone(&usage))),
+            eviction_policy: Mutex::new((config.eviction_policy)()),
+        }).collect();
+
+        let cache = Arc::new(CacheDb {
+            addrs,
+            shards,
+            shard_hasher: RandomState::new(),
+            usage,
+            max_entries: config.max_entries,
+            max_bytes: config.max_bytes,
+            evictions: AtomicU64::new(0),
+            frame_mode: config.frame_mode,
+            max_frame_size: config.max_frame_size,
+            server_identity: config.server_identity,
+            handshake_timeout: config.handshake_timeout,
+            server_event_hook: config.server_event_hook,
+            unix_socket_path: config.unix_socket_path,
+            write_timeout: config.write_timeout,
+            tracking: Mutex::new(KeyTracking{next_conn_id: 0, conns: HashMap::new(), key_conns: HashMap::new(), key_watchers: HashMap::new(), prefix_watchers: HashMap::new()}),
+            tracked_keys: AtomicUsize::new(0),
+            watches: AtomicUsize::new(0),
+        });
+        CacheDb::expiry_sweeper(&cache);
+        cache
+    }
+
+    pub fn stats(&self) -> CacheDbStats {
+        CacheDbStats {
+            entries: self.usage.entries.load(Ordering::Relaxed),
+            bytes: self.usage.bytes.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    fn is_bounded(&self) -> bool {
+        self.max_entries.is_some() || self.max_bytes.is_some()
+    }
+
+    fn exceeds_limits(&self) -> bool {
+        self.max_entries.is_some_and(|max_entries| self.usage.entries.load(Ordering::Relaxed) > max_entries) ||
+            self.max_bytes.is_some_and(|max_bytes| self.usage.bytes.load(Ordering::Relaxed) > max_bytes)
+    }
+
+    fn shard_index(&self, key: &KeyT) -> usize {
+        (self.shard_hasher.hash_one(key) % self.shards.len() as u64) as usize
+    }
+
+    fn shard(&self, key: &KeyT) -> &Shard<KeyT, ValT> {
+        &self.shards[self.shard_index(key)]
+    }
+
+    // indices of the keys grouped by their shard, so that every shard is locked once per batch
+    fn group_by_shard<'a>(&self, keys: impl Iterator<Item = &'a KeyT>) -> Vec<Vec<usize>> where KeyT: 'a {
+        let mut shard_indices = vec![Vec::new(); self.shards.len()];
+        for (i, key) in keys.enumerate() {
+            shard_indices[self.shard_index(key)].push(i);
+        }
+        shard_indices
+    }
+
+    fn entry_size(obj: &KeyValObj<KeyT, ValT>) -> usize {
+        let key_size = obj.key.get_size().map(|size| size as usize).unwrap_or_else(|_| obj.key.get_bytes().len());
+        let val_size = obj.val.get_size().map(|size| size as usize).unwrap_or_else(|_| obj.val.get_bytes().len());
+        key_size + val_size
+    }
+
+    // evicts keys until the CacheDb is within its limits again, starting with the shard the keys have been pushed to
+    // the keys that caused the eviction are never evicted, a batch that exceeds the limits on its own is kept as a whole
+    // must not be called while holding a shard lock, the victims can be in any shard
+    fn evict(&self, shard_index: usize, pushed_keys: &[KeyT]) {
+        if !self.is_bounded() {
+            return;
+        }
+        let mut evicted_keys = Vec::new();
+        for i in 0..self.shards.len() {
+            if !self.exceeds_limits() {
+                break;
+            }
+            let shard = &self.shards[(shard_index + i) % self.shards.len()];
+            let mut key_val_store = shard.key_val_store.write().unwrap();
+            let mut eviction_policy = shard.eviction_policy.lock().unwrap();
+            let mut skipped_keys = Vec::new();
+            while self.exceeds_limits() {
+                match eviction_policy.victim() {
+                    Some(victim) if pushed_keys.contains(&victim) => skipped_keys.push(victim),
+                    Some(victim) => {
+                        if key_val_store.remove(&victim).is_some() {
+                            self.evictions.fetch_add(1, Ordering::Relaxed);
+                            evicted_keys.push(victim);
+                        }
+                    }
+                    None => break
+                }
+            }
+            for key in skipped_keys {
+                eviction_policy.on_insert(&key);
+            }
+        }
+        self.invalidate(evicted_keys.iter());
+    }
+
+    // replaces the value if the key already exists
+    pub fn push(&self, obj: KeyValObj<KeyT, ValT>) {
+        self.push_with_mode(obj, PushMode::Upsert, None);
+    }
+
+    // the key expires after ttl and is no longer visible to get
+    pub fn push_with_ttl(&self, obj: KeyValObj<KeyT, ValT>, ttl: Duration) {
+        self.push_with_mode(obj, PushMode::Upsert, Some(ttl));
+    }
+
+    // returns wether the obj has been stored, expired keys are treated as non existent
+    pub fn push_with_mode(&self, obj: KeyValObj<KeyT, ValT>, mode: PushMode, ttl: Option<Duration>) -> bool {
+        let key = obj.key.clone();
+        let shard_index = self.shard_index(&key);
+        let shard = &self.shards[shard_index];
+        let stored = {
+            let mut key_val_store = shard.key_val_store.write().unwrap();
+            let stored = self.push_locked(shard, &mut key_val_store, obj, mode, ttl, Instant::now());
+            if stored {
+                self.notify(&key_val_store, std::iter::once(&key));
+            }
+            stored
+        };
+        if stored {
+            self.evict(shard_index, std::slice::from_ref(&key));
+            self.invalidate(std::iter::once(&key));
+        }
+        stored
+    }
+
+    // stores all objs with the same ttl (replacing the values of existing keys), every shard is locked once
+    pub fn push_many(&self, objs: Vec<KeyValObj<KeyT, ValT>>, ttl: Option<Duration>) {
+        let now = Instant::now();
+        let keys: Vec<KeyT> = objs.iter().map(|obj| obj.key.clone()).collect();
+        let shard_indices = self.group_by_shard(keys.iter());
+        let mut objs: Vec<Option<KeyValObj<KeyT, ValT>>> = objs.into_iter().map(Some).collect();
+        for (shard, indices) in self.shards.iter().zip(shard_indices) {
+            if indices.is_empty() {
+                continue;
+            }
+            let mut key_val_store = shard.key_val_store.write().unwrap();
+            for &i in indices.iter() {
+                if let Some(obj) = objs[i].take() {
+                    self.push_locked(shard, &mut key_val_store, obj, PushMode::Upsert, ttl, now);
+                }
+            }
+            self.notify(&key_val_store, indices.iter().map(|&i| &keys[i]));
+        }
+        if let Some(first_key) = keys.first() {
+            self.evict(self.shard_index(first_key), &keys);
+        }
+        self.invalidate(keys.iter());
+    }
+
+    // the caller has to evict once the shard is unlocked
+    fn push_locked(&self, shard: &Shard<KeyT, ValT>, key_val_store: &mut KeyValStore<KeyT, ValT>, obj: KeyValObj<KeyT, ValT>, mode: PushMode, ttl: Option<Duration>, now: Instant) -> bool {
+        let exists = key_val_store.entries.get(&obj.key).is_some_and(|entry| !entry.is_expired(now));
+        match (mode, exists) {
+            (PushMode::InsertOnly, true) | (PushMode::UpdateOnly, false) => false,
+            _ => {
+                let key = obj.key.clone();
+                let expires_at = ttl.map(|ttl| now + ttl);
+                let size = CacheDb::entry_size(&obj);
+                let replaced = key_val_store.insert(key.clone(), CacheEntry{obj, expires_at, size});
+                if self.is_bounded() {
+                    let mut eviction_policy = shard.eviction_policy.lock().unwrap();
+                    if replaced.is_some() {
+                        eviction_policy.on_access(&key);
+                    } else {
+                        eviction_policy.on_insert(&key);
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    pub fn get(&self, key: &KeyT) -> Option<KeyValObj<KeyT, ValT>> {
+        let shard = self.shard(key);
+        self.get_locked(shard, &shard.key_val_store.read().unwrap(), key, Instant::now())
+    }
+
+    // returns the objs in the order of the keys, None for keys that could not be found, every shard is locked once
+    pub fn get_many(&self, keys: &[KeyT]) -> Vec<Option<KeyValObj<KeyT, ValT>>> {
+        let now = Instant::now();
+        let mut objs = vec![None; keys.len()];
+        for (shard, indices) in self.shards.iter().zip(self.group_by_shard(keys.iter())) {
+            if indices.is_empty() {
+                continue;
+            }
+            let key_val_store = shard.key_val_store.read().unwrap();
+            for i in indices {
+                objs[i] = self.get_locked(shard, &key_val_store, &keys[i], now);
+            }
+        }
+        objs
+    }
+
+    fn get_locked(&self, shard: &Shard<KeyT, ValT>, key_val_store: &KeyValStore<KeyT, ValT>, key: &KeyT, now: Instant) -> Option<KeyValObj<KeyT, ValT>> {
+        match key_val_store.entries.get(key) {
+            Some(entry) if !entry.is_expired(now) => {
+                if self.is_bounded() {
+                    shard.eviction_policy.lock().unwrap().on_access(key);
+                }
+                Some(entry.obj.clone())
+            }
+            _ => None
+        }
+    }
+
+    // returns the remaining ttl of the key, None if the key does not expire
+    pub fn ttl(&self, key: &KeyT) -> Result<Option<Duration>, CacheDbError> {
+        let now = Instant::now();
+        match self.shard(key).key_val_store.read().unwrap().entries.get(key) {
+            Some(entry) if !entry.is_expired(now) => Ok(entry.expires_at.map(|expires_at| expires_at - now)),
+            _ => Err(CacheDbError::KeyNotFound)
+        }
+    }
+
+    // returns wether the key existed
+    pub fn remove(&self, key: &KeyT) -> bool {
+        let shard = self.shard(key);
+        let removed = {
+            let mut key_val_store = shard.key_val_store.write().unwrap();
+            let removed = key_val_store.remove(key);
+            if removed.is_some() {
+                if self.is_bounded() {
+                    shard.eviction_policy.lock().unwrap().on_remove(key);
+                }
+                self.notify(&key_val_store, std::iter::once(key));
+            }
+            removed
+        };
+        match removed {
+            Some(entry) => {
+                self.invalidate(std::iter::once(key));
+                !entry.is_expired(Instant::now())
+            }
+            None => false
+        }
     }
 
-    pub fn push(&self, obj: KeyValObj<KeyT, ValT>) -> Result<(), CacheDbError> {
-        let send_buff = CacheProtocol::assemble_buff(ProtOpCode::PushOp, &obj)?;
-        if let Err(_) = self.tcp_conn.write().unwrap().write(&send_buff) {
-            return Err(CacheDbError::NetworkError);
-        }
+    // keeps the ttl of the key
+    pub fn set(&self, key: KeyT, val: ValT) -> Result<(), CacheDbError> {
+        self.set_locked(&key, val)?;
+        self.evict(self.shard_index(&key), std::slice::from_ref(&key));
+        self.invalidate(std::iter::once(&key));
         Ok(())
     }
 
-    pub fn pull(&self, key: &KeyT, res: &mut KeyValObj<KeyT, ValT>) -> Result<(), CacheDbError> {
-        loop {
-            for obj in self.key_val_sync_store.read().unwrap().iter() {
-                if obj.key_val.read().unwrap().0.key == *key {
-                    let mut _pull_sig_lock = obj.pulling.lock().unwrap();
-
-                    // if it is not yet requesting val; we do so
-                    // if it was already requested(obj.pulling = true) by somebody else we don't need to do so again
-                    if !*_pull_sig_lock {
-                        let send_buff = CacheProtocol::assemble_buff(ProtOpCode::PullOp, &KeyValObj{key: (*key).clone(), val: ValT::default()})?;
-                        if let Err(_) = self.tcp_conn.write().unwrap().write(&send_buff) {
-                            return Err(CacheDbError::NetworkError);
-                        }
+    fn set_locked(&self, key: &KeyT, val: ValT) -> Result<(), CacheDbError> {
+        let shard = self.shard(key);
+        let mut key_val_store = shard.key_val_store.write().unwrap();
+        let key_val_store = &mut *key_val_store;
+        match key_val_store.entries.get_mut(key) {
+            Some(entry) if !entry.is_expired(Instant::now()) => {
+                entry.obj.val = val;
+                let size = CacheDb::entry_size(&entry.obj);
+                key_val_store.usage.bytes.fetch_add(size, Ordering::Relaxed);
+                key_val_store.usage.bytes.fetch_sub(entry.size, Ordering::Relaxed);
+                entry.size = size;
+                if self.is_bounded() {
+                    shard.eviction_policy.lock().unwrap().on_access(key);
+                }
+                self.notify(key_val_store, std::iter::once(key));
+                Ok(())
+            }
+            _ => Err(CacheDbError::KeyNotFound)
+        }
+    }
 
-                        // is set back to negative by the cache_client_handler on request reply
-                        *_pull_sig_lock = true;
-                        obj.pulling_sig.notify_one();
-                    } 
-
-                    // waiting for pulling to turn to false
-                    // if-block above ensures that this loop is reached 1. only if there has been a request made by this method (if !obj.pulling) or
-                    // 2. if it was true (obj.pulling), it has been true already which indicates that there has been a request made by somebody else
-                    while *_pull_sig_lock {
-                        let _pull_sig_lock = obj.pulling_sig.wait_timeout(_pull_sig_lock, CACHE_CLIENT_REQ_SIG_WAIT).unwrap();
-                        if _pull_sig_lock.1.timed_out() {
-                            return Err(CacheDbError::NetworkTimeOutError);
-                        }
-                        if obj.key_val.read().unwrap().1 {
-                            return Err(CacheDbError::KeyNotFound);
+    // expired keys are collected while only holding the read lock and then removed in small batches,
+    // so readers are never blocked for the duration of a whole sweep
+    fn sweep_expired(&self) {
+        for shard in self.shards.iter() {
+            let now = Instant::now();
+            let expired_keys: Vec<KeyT> = shard.key_val_store.read().unwrap().entries.iter()
+                .filter(|(_, entry)| entry.is_expired(now))
+                .map(|(key, _)| key.clone())
+                .collect();
+
+            for expired_keys_batch in expired_keys.chunks(CACHE_DB_SWEEP_BATCH_SIZE) {
+                let mut removed_keys = Vec::new();
+                {
+                    let mut key_val_store = shard.key_val_store.write().unwrap();
+                    for key in expired_keys_batch {
+                        // the key could have been pushed again in the meantime
+                        if key_val_store.entries.get(key).is_some_and(|entry| entry.is_expired(now)) {
+                            key_val_store.remove(key);
+                            if self.is_bounded() {
+                                shard.eviction_policy.lock().unwrap().on_remove(key);
+                            }
+                            removed_keys.push(key);
                         }
-
-                        // obj.pulling has been set to false by the cache_client_handler and can now be read from the key_val_sync_store
-                        *res = KeyValObj{key: obj.key_val.read().unwrap().0.key.clone(), val: obj.key_val.read().unwrap().0.val.clone()};
-                        return Ok(());
                     }
                 }
+                // the keys are no longer tracked and near caches drop them before their own ttl
+                self.invalidate(removed_keys.into_iter());
             }
-            self.key_val_sync_store.write().unwrap().push(KeyValObjSync{pulling: Mutex::new(false), pulling_sig: Condvar::new(), key_val: RwLock::new(KeyValObjSyncLocked(KeyValObj{key: (*key).clone(), val: ValT::default()}, false))});            
         }
     }
 
-    pub fn pull_async(cache_client: &Arc<CacheClient<KeyT, ValT>>, key: &KeyT) -> JoinHandle<Result<KeyValObj<KeyT, ValT>, CacheDbError>> {
-        let cache_client = cache_client.clone();
-        let key = (*key).clone();
-        return thread::spawn(move || {
+    // the sweeper only holds a weak reference and stops once the cache is dropped
+    fn expiry_sweeper(cache: &Arc<CacheDb<KeyT, ValT>>) {
+        let cache_ref: Weak<CacheDb<KeyT, ValT>> = Arc::downgrade(cache);
+        thread::spawn(move || {
             loop {
-                for obj in cache_client.key_val_sync_store.read().unwrap().iter() {
-                    if obj.key_val.read().unwrap().0.key == key {
-                        let mut _pull_sig_lock = obj.pulling.lock().unwrap();
-
-                        // if it is not yet requesting val; we do so
-                        // if is was already requested(obj.pulling = true) by somebody else we don't need to repeat
-                        if !*_pull_sig_lock {
-                            let send_buff = CacheProtocol::assemble_buff(ProtOpCode::PullOp, &KeyValObj{key: key.clone(), val: ValT::default()})?;
-                            if let Err(_) = cache_client.tcp_conn.write().unwrap().write(&send_buff) {
-                                return Err(CacheDbError::NetworkError);
-                            }
-
-                            // is set back to negative by the cache_client_handler on request reply
-                            *_pull_sig_lock = true;
-                            obj.pulling_sig.notify_one();
-                        }
-
-                        // waiting for pulling to turn to false
-                        // if block above ensures that this loop is reached 1. only if there has been a request made by this method (if !obj.pulling) or
-                        // 2. if it was true (obj.pulling), it has been true already which indicates that there has been a request made by somebody else
-                        while *_pull_sig_lock {
-                            let _pull_sig_lock = obj.pulling_sig.wait_timeout(_pull_sig_lock, CACHE_CLIENT_REQ_SIG_WAIT).unwrap();
-                            if _pull_sig_lock.1.timed_out() {
-                                return Err(CacheDbError::NetworkTimeOutError);
-                            }
-                            // obj.pulling has been set to false by the cache_client_handler and can now be read from the key_val_sync_store
-                            return Ok(KeyValObj{key: obj.key_val.read().unwrap().0.key.clone(), val: obj.key_val.read().unwrap().0.val.clone()});
-                        }
-                        return Err(CacheDbError::NetworkError);
-                    }
+                thread::sleep(CACHE_DB_SWEEP_INTERVAL);
+                match cache_ref.upgrade() {
+                    Some(cache) => cache.sweep_expired(),
+                    None => return
                 }
-                cache_client.key_val_sync_store.write().unwrap().push(KeyValObjSync{pulling: Mutex::new(false), pulling_sig: Condvar::new(), key_val: RwLock::new(KeyValObjSyncLocked(KeyValObj{key: key.clone(), val: ValT::default()}, false))});
             }
         });
     }
 
+    // returns the id of the connection, its pulled keys and watches are tracked until untrack_conn
+    fn track_conn(&self, writer: &Arc<Mutex<ConnStream>>, closer: ConnStream) -> u64 {
+        let mut tracking = self.tracking.lock().unwrap();
+        let conn_id = tracking.next_conn_id;
+        tracking.next_conn_id += 1;
+        tracking.conns.insert(conn_id, TrackedConn{outbound: None, writer: Arc::clone(writer), closer: Arc::new(closer), keys: HashSet::new(), watched_keys: HashSet::new(), watched_prefixes: HashSet::new()});
+        conn_id
+    }
 
-    pub fn terminate_conn(&mut self) -> io::Result<usize> {
-        // op_code 4 -> terminate_conn
-        // key/val size 0/ 0
-        let term_seq: [u8; 5] = [4, 0, 0 ,0 ,0];
-        self.tcp_conn.write().unwrap().write(&term_seq)
+    fn untrack_conn(&self, conn_id: u64) {
+        let mut tracking = self.tracking.lock().unwrap();
+        let tracking = &mut *tracking;
+        if let Some(conn) = tracking.conns.remove(&conn_id) {
+            for key in conn.keys {
+                remove_conn_id(&mut tracking.key_conns, &key, conn_id);
+            }
+            self.tracked_keys.store(tracking.key_conns.len(), Ordering::SeqCst);
+            for key in conn.watched_keys {
+                remove_conn_id(&mut tracking.key_watchers, &key, conn_id);
+            }
+            for prefix in conn.watched_prefixes {
+                remove_conn_id(&mut tracking.prefix_watchers, &prefix, conn_id);
+            }
+            self.watches.store(tracking.key_watchers.len() + tracking.prefix_watchers.len(), Ordering::SeqCst);
+        }
     }
 
-    pub fn cache_client_handler(cache_client: &Arc<CacheClient<KeyT, ValT>>) -> JoinHandle<Result<(), CacheDbError>> {
-        let ccache_clone = Arc::clone(cache_client);
-        thread::spawn(move || {
-            let mut buff = [0; TCP_READ_BUFF_SIZE];
+    // handles WatchOp, WatchPrefixOp, UnwatchOp and UnwatchPrefixOp, prefixes are compared by the bytes of the keys
+    fn watch(&self, conn_id: u64, op_code: ProtOpCode, key: &KeyT) {
+        let mut tracking = self.tracking.lock().unwrap();
+        let tracking = &mut *tracking;
+        let Some(conn) = tracking.conns.get_mut(&conn_id) else {
+            return;
+        };
+        match op_code {
+            ProtOpCode::WatchOp => {
+                conn.start_outbound();
+                conn.watched_keys.insert(key.clone());
+                tracking.key_watchers.entry(key.clone()).or_default().insert(conn_id);
+            }
+            ProtOpCode::WatchPrefixOp => {
+                conn.start_outbound();
+                conn.watched_prefixes.insert(key.get_bytes());
+                tracking.prefix_watchers.entry(key.get_bytes()).or_default().insert(conn_id);
+            }
+            ProtOpCode::UnwatchOp => {
+                conn.watched_keys.remove(key);
+                remove_conn_id(&mut tracking.key_watchers, key, conn_id);
+            }
+            ProtOpCode::UnwatchPrefixOp => {
+                conn.watched_prefixes.remove(&key.get_bytes());
+                remove_conn_id(&mut tracking.prefix_watchers, &key.get_bytes(), conn_id);
+            }
+            _ => {}
+        }
+        self.watches.store(tracking.key_watchers.len() + tracking.prefix_watchers.len(), Ordering::SeqCst);
+    }
 
-            let mut parser = CacheProtocol::<KeyT, ValT>::new();
-            let mut parsed_op_code: ProtOpCode = ProtOpCode::PullOp;
-            let mut parsed_obj: KeyValObj<KeyT, ValT> = KeyValObj {
-                key: KeyT::default(),
-                val: ValT::default(),
-            };
-            let mut tcp_read_size: usize;
-            let mut buff_left_over_size: usize = 0;
-            let mut cloned_socket = ccache_clone.tcp_conn.write().unwrap().try_clone().unwrap();
-            'tcp_read: loop {
-                if buff_left_over_size == 0 {
-                    match cloned_socket.read(&mut buff) {
-                        Err(_) => return Err(CacheDbError::NetworkError),
-                        Ok(size) => tcp_read_size = size
-                    }
-                } else {
-                    match cloned_socket.read(&mut buff[buff_left_over_size..]) {
-                        Err(_) => return Err(CacheDbError::NetworkError),
-                        Ok(size) => tcp_read_size = size
-                    }
+    // connections that watch the key or a prefix of it
+    fn watchers(tracking: &KeyTracking<KeyT>, key: &KeyT) -> HashSet<u64> {
+        let mut watchers = tracking.key_watchers.get(key).cloned().unwrap_or_default();
+        if !tracking.prefix_watchers.is_empty() {
+            let key_bytes = key.get_bytes();
+            for (prefix, conn_ids) in tracking.prefix_watchers.iter() {
+                if key_bytes.starts_with(prefix) {
+                    watchers.extend(conn_ids);
                 }
-                
-                if tcp_read_size == 0 {
+            }
+        }
+        watchers
+    }
+
+    fn track_key(&self, conn_id: u64, key: &KeyT) {
+        let mut tracking = self.tracking.lock().unwrap();
+        if let Some(conn) = tracking.conns.get_mut(&conn_id) {
+            conn.start_outbound();
+            conn.keys.insert(key.clone());
+            tracking.key_conns.entry(key.clone()).or_default().insert(conn_id);
+            self.tracked_keys.store(tracking.key_conns.len(), Ordering::SeqCst);
+        }
+    }
+
+    fn untrack_key(&self, conn_id: u64, key: &KeyT) {
+        let mut tracking = self.tracking.lock().unwrap();
+        if let Some(conn) = tracking.conns.get_mut(&conn_id) {
+            conn.keys.remove(key);
+        }
+        remove_conn_id(&mut tracking.key_conns, key, conn_id);
+        self.tracked_keys.store(tracking.key_conns.len(), Ordering::SeqCst);
+    }
+
+    // sends an InvalidateOp to every connection that pulled one of the keys, the keys are no longer tracked for them
+    // the key is tracked before the pull reads it, so a change that is not seen here has been stored before the pull read the key
+    fn invalidate<'a>(&self, keys: impl Iterator<Item = &'a KeyT>) where KeyT: 'a {
+        if self.tracked_keys.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let mut invalidations: HashMap<u64, TrackedSend> = HashMap::new();
+        {
+            let mut tracking = self.tracking.lock().unwrap();
+            let tracking = &mut *tracking;
+            for key in keys {
+                let Some(conn_ids) = tracking.key_conns.remove(key) else {
                     continue;
+                };
+                let Ok(invalidate_frame) = CacheProtocol::assemble_buff(self.frame_mode, ProtOpCode::InvalidateOp, 0, &KeyValObj{key: key.clone(), val: ValT::default()}, None) else {
+                    continue;
+                };
+                for conn_id in conn_ids {
+                    if let Some(conn) = tracking.conns.get_mut(&conn_id) {
+                        conn.keys.remove(key);
+                        conn.queue_frame(conn_id, &invalidate_frame, &mut invalidations);
+                    }
                 }
+            }
+            self.tracked_keys.store(tracking.key_conns.len(), Ordering::SeqCst);
+        }
+        CacheDb::<KeyT, ValT>::send_tracked(invalidations);
+    }
 
-                tcp_read_size += buff_left_over_size;
-                
-                // reset buffer parsing pointer only if there is no left over buffer and 
-                // if the parsing is completed
-                if buff_left_over_size == 0 && parser.parsed_protocoll_segment == 0 {
-                    parser.parsed_bytes_total = 0;
-                    parser.to_parse_bytes_total = 0;
-                } else {
-                    // set to_parse_bytes_total to absolute size
-                    parser.to_parse_bytes_total = parser.to_parse_bytes_total - parser.parsed_bytes_total;
-                    parser.parsed_bytes_total = 0;
-                }
-
-                loop {
-                    match parser.parse_buff(&mut buff, tcp_read_size, &mut parsed_op_code, &mut parsed_obj)? {
-                        // check wether parse_buff is done(-> can't parse the buffer any further without next tcp buff read)
-                        (parsed, left_over_size) if !parsed => {
-                            buff_left_over_size = left_over_size;
-                            break;
-                        },
-                        (parsed, _) if parsed => {
-                            // successfully parsed parsed_obj is now updated to latest parsed obj (such as parsed_op_code)
-                            match parsed_op_code {
-                                ProtOpCode::TerminateConn => {
-                                    break 'tcp_read;
-                                },
-                                ProtOpCode::PullReplyOp | ProtOpCode::PullReplyNotFoundOp => {
-                                    for obj in ccache_clone.key_val_sync_store.read().unwrap().iter() {
-                                        if obj.key_val.read().unwrap().0.key == parsed_obj.key {
-                                            obj.key_val.write().unwrap().0.val = parsed_obj.val.clone();
-                                            if parsed_op_code == ProtOpCode::PullReplyNotFoundOp {
-                                                obj.key_val.write().unwrap().1 = true;
-                                            }
-                                            *obj.pulling.lock().unwrap() = false;
-                                            obj.pulling_sig.notify_one();
-                                        }
-                                    }
-                                },
-                                _ => {
-                                    break 'tcp_read;
-                                }
-                            }
-                            continue;
-                        },
-                        (_, _) => {
-                            break;
-                        }
+    // sends a ChangeNotifyOp with the stored val (or a ChangeNotifyDeletedOp) to every connection that watches one of the changed keys
+    // called while the key_val_store is still locked, so that the notifications of a key are queued in the order of its changes
+    // and a watch that has been acknowledged before the change is never missed
+    // the tracking is never locked before a shard, and the frames are only queued for the outbound threads
+    fn notify<'a>(&self, key_val_store: &KeyValStore<KeyT, ValT>, keys: impl Iterator<Item = &'a KeyT>) where KeyT: 'a {
+        if self.watches.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let mut notifications: HashMap<u64, TrackedSend> = HashMap::new();
+        {
+            let tracking = self.tracking.lock().unwrap();
+            for key in keys {
+                let watchers = Self::watchers(&tracking, key);
+                if watchers.is_empty() {
+                    continue;
+                }
+                let notify_frame = match key_val_store.entries.get(key) {
+                    Some(entry) => CacheProtocol::assemble_buff(self.frame_mode, ProtOpCode::ChangeNotifyOp, 0, &entry.obj, None),
+                    None => CacheProtocol::assemble_buff(self.frame_mode, ProtOpCode::ChangeNotifyDeletedOp, 0, &KeyValObj{key: key.clone(), val: ValT::default()}, None),
+                };
+                let Ok(notify_frame) = notify_frame else {
+                    continue;
+                };
+                for conn_id in watchers {
+                    if let Some(conn) = tracking.conns.get(&conn_id) {
+                        conn.queue_frame(conn_id, &notify_frame, &mut notifications);
                     }
                 }
             }
-            return Ok(())
-        })
+        }
+        CacheDb::<KeyT, ValT>::send_tracked(notifications);
     }
-}
 
-impl<KeyT: 'static, ValT: 'static> CacheDb<KeyT, ValT> where KeyT: PartialEq + GenericKeyVal<KeyT> + Default + Debug + Send + Sync + Clone, ValT: GenericKeyVal<ValT> + Default + Debug + Send + Sync, KeyValObj<KeyT, ValT>: Clone {
-    pub fn new(ipv4_addr: [u8; 4], port: u16) -> Arc<CacheDb<KeyT, ValT>> {
-        let cache = CacheDb {
-            ipv4_addr: ipv4_addr,
-            port: port,
-            key_val_store: RwLock::new(Vec::new()),
-        };
-        Arc::new(cache)
+    // queues the frames for the outbound threads, a connection that does not keep up with its frames is closed and served no longer
+    fn send_tracked(send_buffs: HashMap<u64, TrackedSend>) {
+        for (outbound, closer, send_buff) in send_buffs.into_values() {
+            if outbound.try_send(send_buff).is_err() {
+                let _ = closer.shutdown(Shutdown::Both);
+            }
+        }
     }
 
-    pub fn push(&self, obj: KeyValObj<KeyT, ValT>) {
-        self.key_val_store.write().unwrap().push(Box::<KeyValObj<KeyT, ValT>>::new(obj));
+    fn emit_event(&self, event: ServerEvent) {
+        if let Some(server_event_hook) = &self.server_event_hook {
+            server_event_hook(&event);
+        }
+    }
 
+    fn client_handler(socket: &mut ConnStream, peer_addr: ConnAddr, cache: &Arc<CacheDb<KeyT, ValT>>, state: &Arc<ServerState>) {
+        let reason = CacheDb::serve_client(socket, &peer_addr, cache, state);
+        cache.emit_event(ServerEvent::Closed(peer_addr, reason));
     }
 
-    // returning reference since there is a lifetime from the CacheDb(self) struct
-    pub fn get(&self, key: &KeyT) -> Option<KeyValObj<KeyT, ValT>> {
-        for obj in self.key_val_store.read().unwrap().iter() {
-            if &obj.key == key {
-                return Some((**obj).clone());
-            }
-        }
-        None
+    // the connection is closed after the hello reply if the client is not compatible, returns the hello of the client
+    fn handshake(socket: &mut ConnStream, cache: &CacheDb<KeyT, ValT>) -> Result<ProtHello, ConnCloseReason> {
+        socket.set_read_timeout(Some(cache.handshake_timeout)).map_err(|e| ConnCloseReason::IoError(e.kind()))?;
+        let client_hello = ProtHello::read_hello(socket, ProtOpCode::HelloOp).map_err(ConnCloseReason::HandshakeFailed)?;
+        socket.set_read_timeout(None).map_err(|e| ConnCloseReason::IoError(e.kind()))?;
+        let mut server_hello = ProtHello::new(cache.frame_mode, cache.server_identity.clone());
+        server_hello.capabilities |= CACHE_PROTOCOL_CAP_INVALIDATION;
+        let hello_reply = server_hello.assemble_buff(ProtOpCode::HelloReplyOp).map_err(ConnCloseReason::HandshakeFailed)?;
+        socket.write_all(&hello_reply).map_err(|e| ConnCloseReason::IoError(e.kind()))?;
+        client_hello.check_compatible(&server_hello).map_err(ConnCloseReason::HandshakeFailed)?;
+        Ok(client_hello)
     }
 
-    pub fn set(&self, key: KeyT, val: ValT) -> Result<(), CacheDbError> {
-        for obj in self.key_val_store.write().unwrap().iter_mut() {
-            if obj.key == key {
-                obj.val = val;
-                return Ok(());
-            }
+    // replies to a request that could not be handled
+    fn write_error_reply(writer: &Mutex<ConnStream>, cache: &CacheDb<KeyT, ValT>, req_id: u32, error: &CacheDbError, message: &str) -> Result<(), ConnCloseReason> {
+        let error_reply = CacheProtocol::<KeyT, ValT>::assemble_error_reply(cache.frame_mode, req_id, error, message)
+            .map_err(ConnCloseReason::ProtocolError)?;
+        writer.lock().unwrap().write_all(&error_reply).map_err(|e| ConnCloseReason::IoError(e.kind()))
+    }
+
+    // serves the requests of a client until the connection is closed and returns why it was closed
+    fn serve_client(socket: &mut ConnStream, peer_addr: &ConnAddr, cache: &Arc<CacheDb<KeyT, ValT>>, state: &Arc<ServerState>) -> ConnCloseReason {
+        let client_hello = match CacheDb::handshake(socket, cache) {
+            Ok(client_hello) => client_hello,
+            Err(reason) => return reason,
+        };
+        // all frames are written through the writer, since InvalidateOps and ChangeNotifyOps are written by the outbound thread of the connection as well
+        // a client that does not read its frames is closed once the write timeout expired
+        let (writer, closer) = match (socket.try_clone(), socket.try_clone()) {
+            (Ok(writer), Ok(closer)) => (writer, closer),
+            (Err(e), _) | (_, Err(e)) => return ConnCloseReason::IoError(e.kind()),
+        };
+        if let Err(e) = writer.set_write_timeout(cache.write_timeout) {
+            return ConnCloseReason::IoError(e.kind());
         }
-        Err(CacheDbError::KeyNotFound)
+        let writer = Arc::new(Mutex::new(writer));
+        cache.emit_event(ServerEvent::Connected(peer_addr.clone()));
+
+        // the keys pulled by clients with a near cache and the watches are tracked until the connection is closed
+        let conn_id = cache.track_conn(&writer, closer);
+        let near_cache = client_hello.capabilities & CACHE_PROTOCOL_CAP_INVALIDATION != 0;
+        let reason = CacheDb::serve_requests(socket, &writer, conn_id, near_cache, peer_addr, cache, state);
+        cache.untrack_conn(conn_id);
+        reason
     }
 
-    fn client_handler(mut socket: TcpStream, cache: &Arc<CacheDb<KeyT, ValT>>) {
+    fn serve_requests(socket: &mut ConnStream, writer: &Mutex<ConnStream>, conn_id: u64, near_cache: bool, peer_addr: &ConnAddr, cache: &Arc<CacheDb<KeyT, ValT>>, state: &Arc<ServerState>) -> ConnCloseReason {
         let mut buff = [0; TCP_READ_BUFF_SIZE];
 
-        let mut parser = CacheProtocol::<KeyT, ValT>::new();
+        let mut parser = CacheProtocol::<KeyT, ValT>::with_frame_mode(cache.frame_mode, cache.max_frame_size);
         let mut parsed_op_code: ProtOpCode = ProtOpCode::PullOp;
         let mut parsed_obj: KeyValObj<KeyT, ValT> = KeyValObj {
             key: KeyT::default(),
             val: ValT::default(),
         };
-        let mut tcp_read_size: usize;
-        let mut buff_left_over_size: usize = 0;
-        'tcp_read: loop {
-            if buff_left_over_size == 0 {
-                tcp_read_size = socket.read(&mut buff).unwrap();
-
-            } else {
-                tcp_read_size = socket.read(&mut buff[buff_left_over_size..]).unwrap();
-            }
-            
+        loop {
+            let tcp_read_size = match socket.read(&mut buff) {
+                Ok(size) => size,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return ConnCloseReason::IoError(e.kind()),
+            };
+            // the connection has been closed by the client or its read side by ServerHandle::shutdown
             if tcp_read_size == 0 {
-                continue;
-            }
-
-            tcp_read_size += buff_left_over_size;
-            
-            // reset buffer parsing pointer only if there is no left over buffer and 
-            // if the parsing is completed
-            if buff_left_over_size == 0 && parser.parsed_protocoll_segment == 0 {
-                parser.parsed_bytes_total = 0;
-                parser.to_parse_bytes_total = 0;
-            } else {
-                // set to_parse_bytes_total to absolute size
-                parser.to_parse_bytes_total = parser.to_parse_bytes_total - parser.parsed_bytes_total;
-                parser.parsed_bytes_total = 0;
+                if !state.shutting_down.load(Ordering::SeqCst) {
+                    return ConnCloseReason::Eof;
+                }
+                if let Ok(term_seq) = CacheProtocol::assemble_buff(cache.frame_mode, ProtOpCode::TerminateConn, 0, &KeyValObj{key: KeyT::default(), val: ValT::default()}, None) {
+                    let _ = writer.lock().unwrap().write_all(&term_seq);
+                }
+                return ConnCloseReason::Shutdown;
             }
+            parser.feed(&buff[..tcp_read_size]);
 
+            // parsing all frames that have been received completely
             loop {
-                match parser.parse_buff(&mut buff, tcp_read_size, &mut parsed_op_code, &mut parsed_obj).unwrap() {
-                    // check wether parse_buff is done(-> can't parse the buffer any further without next tcp buff read)
-                    (parsed, left_over_size) if !parsed => {
-                        buff_left_over_size = left_over_size;
-                        break;
+                match parser.parse_buff(&mut parsed_op_code, &mut parsed_obj) {
+                    Ok(true) => {},
+                    Ok(false) => break,
+                    // the frame has been removed from the parser, so the following frames can still be read
+                    Err(CacheDbError::DecodingErr) => {
+                        cache.emit_event(ServerEvent::FrameError(peer_addr.clone(), CacheDbError::DecodingErr));
+                        if let Err(reason) = CacheDb::write_error_reply(writer, cache, parser.parsed_req_id(), &CacheDbError::DecodingErr, "key or val could not be decoded") {
+                            return reason;
+                        }
+                        continue;
+                    }
+                    // the frame boundaries are unknown, so the connection can not be read any further
+                    Err(e) => {
+                        let _ = CacheDb::write_error_reply(writer, cache, 0, &e, "frame could not be parsed, closing connection");
+                        return ConnCloseReason::ProtocolError(e);
+                    }
+                }
+
+                // successfully parsed parsed_obj is now updated to latest parsed obj (such as parsed_op_code)
+                let key_obj = KeyValObj{key: parsed_obj.key.clone(), val: ValT::default()};
+                // replies carry the request id of their request
+                let req_id = parser.parsed_req_id();
+                let reply = match parsed_op_code {
+                    ProtOpCode::TerminateConn => {
+                        return ConnCloseReason::Terminated;
                     },
-                    (parsed, _) if parsed => {
-                        // successfully parsed parsed_obj is now updated to latest parsed obj (such as parsed_op_code)
-                        match parsed_op_code {
-                            ProtOpCode::TerminateConn => {
-                                break 'tcp_read;
-                            },
-                            ProtOpCode::PushOp => {
-                                cache.push(parsed_obj.clone());
+                    ProtOpCode::PushOp | ProtOpCode::PushInsertOp | ProtOpCode::PushUpdateOp => {
+                        let mode = match parsed_op_code {
+                            ProtOpCode::PushInsertOp => PushMode::InsertOnly,
+                            ProtOpCode::PushUpdateOp => PushMode::UpdateOnly,
+                            _ => PushMode::Upsert,
+                        };
+                        let stored = cache.push_with_mode(parsed_obj.clone(), mode, parser.parsed_ttl());
+                        // only pushes with request id are acknowledged
+                        match req_id {
+                            0 => None,
+                            _ => {
+                                let ack_op_code = if stored { ProtOpCode::PushAckOp } else { ProtOpCode::PushAckNotStoredOp };
+                                Some(CacheProtocol::assemble_buff(cache.frame_mode, ack_op_code, req_id, &key_obj, None))
                             }
-                            ProtOpCode::PullOp => {
-                                match cache.get(&parsed_obj.key) {
-                                    Some(obj) => {
-                                        match CacheProtocol::assemble_buff(ProtOpCode::PullReplyOp, &obj) {
-                                            Ok(send_buff) => {
-                                                if let Err(_) = socket.write(&send_buff) {
-                                                    break 'tcp_read;
-                                                }       
-                                            },
-                                            Err(_) => {
-                                                break 'tcp_read;
-                                            }
-                                        }    
-                                    }
-                                    None => {
-                                        match CacheProtocol::assemble_buff(ProtOpCode::PullReplyNotFoundOp, &KeyValObj{key: parsed_obj.key.clone(), val: ValT::default()}) {
-                                            Ok(send_buff) => {
-                                                if let Err(_) = socket.write(&send_buff) {
-                                                    break 'tcp_read;
-                                                }       
-                                            },
-                                            Err(_) => {
-                                                break 'tcp_read;
-                                            }
-                                        }
-                                    }
+                        }
+                    }
+                    ProtOpCode::TtlOp => {
+                        match cache.ttl(&parsed_obj.key) {
+                            Ok(ttl) => Some(CacheProtocol::assemble_buff(cache.frame_mode, ProtOpCode::TtlReplyOp, req_id, &key_obj, ttl)),
+                            Err(_) => Some(CacheProtocol::assemble_buff(cache.frame_mode, ProtOpCode::TtlReplyNotFoundOp, req_id, &key_obj, None)),
+                        }
+                    }
+                    ProtOpCode::DeleteOp => {
+                        let reply_op_code = if cache.remove(&parsed_obj.key) { ProtOpCode::DeleteReplyOp } else { ProtOpCode::DeleteReplyNotFoundOp };
+                        Some(CacheProtocol::assemble_buff(cache.frame_mode, reply_op_code, req_id, &key_obj, None))
+                    }
+                    ProtOpCode::PullOp => {
+                        // tracked before the get, so that every change after the get is invalidated
+                        if near_cache {
+                            cache.track_key(conn_id, &parsed_obj.key);
+                        }
+                        match cache.get(&parsed_obj.key) {
+                            Some(obj) => Some(CacheProtocol::assemble_buff(cache.frame_mode, ProtOpCode::PullReplyOp, req_id, &obj, None)),
+                            None => {
+                                // keys that could not be found are not near cached
+                                if near_cache {
+                                    cache.untrack_key(conn_id, &parsed_obj.key);
                                 }
-                            },
-                            _ => {
-                                break 'tcp_read;
+                                Some(CacheProtocol::assemble_buff(cache.frame_mode, ProtOpCode::PullReplyNotFoundOp, req_id, &key_obj, None))
                             }
                         }
-                        continue;
                     },
-                    (_, _) => {
-                        break;
+                    ProtOpCode::MultiPullOp => {
+                        let keys: Vec<KeyT> = parser.take_parsed_entries().into_iter().map(|(obj, _)| obj.key).collect();
+                        let objs = cache.get_many(&keys);
+                        let entries: Vec<(KeyValObj<KeyT, ValT>, bool)> = keys.into_iter().zip(objs).map(|(key, obj)| match obj {
+                            Some(obj) => (obj, true),
+                            None => (KeyValObj{key, val: ValT::default()}, false),
+                        }).collect();
+                        Some(CacheProtocol::assemble_multi_buff(cache.frame_mode, ProtOpCode::MultiPullReplyOp, req_id, &entries, None))
+                    },
+                    ProtOpCode::MultiPushOp => {
+                        let objs = parser.take_parsed_entries().into_iter().map(|(obj, _)| obj).collect();
+                        cache.push_many(objs, parser.parsed_ttl());
+                        // only pushes with request id are acknowledged
+                        match req_id {
+                            0 => None,
+                            _ => Some(CacheProtocol::assemble_buff(cache.frame_mode, ProtOpCode::PushAckOp, req_id, &key_obj, None)),
+                        }
+                    },
+                    ProtOpCode::WatchOp | ProtOpCode::WatchPrefixOp | ProtOpCode::UnwatchOp | ProtOpCode::UnwatchPrefixOp => {
+                        cache.watch(conn_id, parsed_op_code, &parsed_obj.key);
+                        // only watches with request id are acknowledged
+                        match req_id {
+                            0 => None,
+                            _ => Some(CacheProtocol::assemble_buff(cache.frame_mode, ProtOpCode::WatchAckOp, req_id, &key_obj, None)),
+                        }
+                    },
+                    // replies and hellos are never sent by a client
+                    _ => Some(Err(CacheDbError::ParsingErr)),
+                };
+                let write_res = match reply {
+                    Some(Ok(send_buff)) => writer.lock().unwrap().write_all(&send_buff).map_err(|e| ConnCloseReason::IoError(e.kind())),
+                    // such as a val that is too large for the frame mode
+                    Some(Err(e)) => {
+                        let message = format!("{:?} request could not be handled: {:?}", parsed_op_code, e);
+                        let write_res = CacheDb::write_error_reply(writer, cache, req_id, &e, &message);
+                        cache.emit_event(ServerEvent::FrameError(peer_addr.clone(), e));
+                        write_res
                     }
+                    None => Ok(()),
+                };
+                if let Err(reason) = write_res {
+                    return reason;
                 }
             }
         }
     }
 
-    pub fn cache_db_server(cache: &Arc<CacheDb<KeyT, ValT>>) -> JoinHandle<io::Result<()>> {
-        let cache_clone = Arc::clone(cache);
-
-        thread::spawn(move || {
-            let addr = SocketAddr::from((cache_clone.ipv4_addr, cache_clone.port));
-
+    // the server runs until ServerHandle::shutdown is called, dropping the handle does not stop it
+    // the listeners are bound before returning, so clients can connect to ServerHandle::local_addrs right away
+    // port 0 binds to a free port chosen by the os, fails if any of the addresses can not be bound
+    // the unix socket of the CacheDbConfig is listened on in addition to the addresses, its file is removed on shutdown
+    pub fn cache_db_server(cache: &Arc<CacheDb<KeyT, ValT>>) -> io::Result<ServerHandle> {
+        let mut listeners = Vec::with_capacity(cache.addrs.len() + 1);
+        let mut local_addrs = Vec::with_capacity(cache.addrs.len());
+        for addr in cache.addrs.iter() {
             let listener = TcpListener::bind(addr)?;
-            loop {
-                let (socket, _addr) = listener.accept()?;
-                let thread_cache = Arc::clone(&cache_clone);
-                thread::spawn(move || (CacheDb::<KeyT, ValT>::client_handler(socket, &thread_cache)));
+            local_addrs.push(listener.local_addr()?);
+            listeners.push(ConnListener::Tcp(listener));
+        }
+        // the accept can only be woken up through an address that accepts connections
+        let mut wake_addrs: Vec<ConnAddr> = local_addrs.iter().map(|local_addr| {
+            let mut wake_addr = *local_addr;
+            match wake_addr.ip() {
+                IpAddr::V4(ip) if ip.is_unspecified() => wake_addr.set_ip(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+                IpAddr::V6(ip) if ip.is_unspecified() => wake_addr.set_ip(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+                _ => {}
+            }
+            ConnAddr::Tcp(wake_addr)
+        }).collect();
+        if let Some(path) = &cache.unix_socket_path {
+            listeners.push(ConnListener::bind_unix(path)?);
+            #[cfg(unix)]
+            wake_addrs.push(ConnAddr::Unix(Some(path.clone())));
+        }
 
-                #[cfg(test)]
+        let state = Arc::new(ServerState{shutting_down: AtomicBool::new(false), conns: Mutex::new(Vec::new())});
+        let accept_threads = listeners.into_iter().map(|listener| {
+            let cache_clone = Arc::clone(cache);
+            let state_clone = Arc::clone(&state);
+            thread::spawn(move || CacheDb::accept_loop(listener, &cache_clone, &state_clone))
+        }).collect();
+        Ok(ServerHandle{local_addrs, unix_socket_path: cache.unix_socket_path.clone(), wake_addrs, state, accept_threads})
+    }
+
+    // accepts connections until the server is shut down, every connection is handled by a thread of its own
+    fn accept_loop(listener: ConnListener, cache: &Arc<CacheDb<KeyT, ValT>>, state: &Arc<ServerState>) -> io::Result<()> {
+        loop {
+            let (mut socket, peer_addr) = listener.accept()?;
+            // the accept is woken up by a connection of the ServerHandle
+            if state.shutting_down.load(Ordering::SeqCst) {
                 return Ok(());
             }
-        })
+            let Ok(conn_socket) = socket.try_clone() else {
+                continue;
+            };
+            let thread_cache = Arc::clone(cache);
+            let thread_state = Arc::clone(state);
+            let handler = thread::spawn(move || {
+                CacheDb::<KeyT, ValT>::client_handler(&mut socket, peer_addr, &thread_cache, &thread_state);
+                // the ServerConn holds a clone of the socket, which would keep the connection open
+                let _ = socket.shutdown(Shutdown::Both);
+            });
+
+            let mut conns = state.conns.lock().unwrap();
+            // joining the handlers of closed connections
+            let (closed, open): (Vec<ServerConn>, Vec<ServerConn>) = conns.drain(..).partition(|conn| conn.handler.is_finished());
+            for conn in closed {
+                let _ = conn.handler.join();
+            }
+            *conns = open;
+            conns.push(ServerConn{socket: conn_socket, handler});
+        }
+    }
+}
+
+// connection of a client, the socket is a clone of the one used by its client_handler
+struct ServerConn {
+    socket: ConnStream,
+    handler: JoinHandle<()>,
+}
+
+// shared by the accept loop, the client handlers and the ServerHandle
+struct ServerState {
+    shutting_down: AtomicBool,
+    conns: Mutex<Vec<ServerConn>>,
+}
+
+pub struct ServerHandle {
+    // one listener and accept thread per address (and the unix socket)
+    local_addrs: Vec<SocketAddr>,
+    unix_socket_path: Option<PathBuf>,
+    wake_addrs: Vec<ConnAddr>,
+    state: Arc<ServerState>,
+    accept_threads: Vec<JoinHandle<io::Result<()>>>,
+}
+
+impl ServerHandle {
+    // first address the server is listening on, contains the actual port if the CacheDb was created with port 0
+    // None if the server only listens on a unix socket
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addrs.first().copied()
+    }
+
+    // all tcp addresses the server is listening on, in the order of the addresses of the CacheDb
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
+    }
+
+    pub fn unix_socket_path(&self) -> Option<&Path> {
+        self.unix_socket_path.as_deref()
+    }
+
+    pub fn shutdown(self) -> Result<(), CacheDbError> {
+        self.shutdown_with_timeout(CACHE_DB_SHUTDOWN_TIMEOUT)
+    }
+
+    // stops accepting connections and closes all client connections, clients receive a TerminateConn frame
+    // after the replies to all requests they have sent so far
+    // fails with NetworkTimeOutError if not all client handlers finished within timeout, their connections are then closed forcefully
+    pub fn shutdown_with_timeout(self, timeout: Duration) -> Result<(), CacheDbError> {
+        let deadline = Instant::now() + timeout;
+        self.state.shutting_down.store(true, Ordering::SeqCst);
+
+        // waking up the accepts, an accept thread has already stopped if it fails
+        for (wake_addr, accept_thread) in self.wake_addrs.iter().zip(self.accept_threads) {
+            let woken = match wake_addr {
+                ConnAddr::Tcp(wake_addr) => TcpStream::connect_timeout(wake_addr, timeout).is_ok(),
+                #[cfg(unix)]
+                ConnAddr::Unix(_) => ConnStream::connect(wake_addr, None).is_ok(),
+            };
+            if woken {
+                let _ = accept_thread.join();
+            }
+        }
+        #[cfg(unix)]
+        if let Some(path) = &self.unix_socket_path {
+            let _ = fs::remove_file(path);
+        }
+
+        // the client handlers read the remaining requests, reply to them and then terminate the connection
+        let mut conns = std::mem::take(&mut *self.state.conns.lock().unwrap());
+        for conn in conns.iter() {
+            let _ = conn.socket.shutdown(Shutdown::Read);
+        }
+        while Instant::now() < deadline && conns.iter().any(|conn| !conn.handler.is_finished()) {
+            thread::sleep(CACHE_DB_SHUTDOWN_POLL_INTERVAL);
+        }
+
+        let mut timed_out = false;
+        for conn in conns.drain(..) {
+            if conn.handler.is_finished() {
+                let _ = conn.handler.join();
+            } else {
+                let _ = conn.socket.shutdown(Shutdown::Both);
+                timed_out = true;
+            }
+        }
+        if timed_out {
+            return Err(CacheDbError::NetworkTimeOutError);
+        }
+        Ok(())
+    }
+
+    // blocks until the server stopped accepting connections on all addresses, returns the first error of an accept thread
+    pub fn join(self) -> io::Result<()> {
+        let mut res = Ok(());
+        for accept_thread in self.accept_threads {
+            let accept_res = accept_thread.join().unwrap_or_else(|_| Err(io::Error::other("accept thread panicked")));
+            if res.is_ok() {
+                res = accept_res;
+            }
+        }
+        res
     }
 }
 
@@ -574,13 +3483,13 @@ mod tests {
     use super::*;
 
     impl GenericKeyVal<String> for String {
-        fn get_size(self: &String) -> Result<u16, CacheDbError> {
+        fn get_size(self: &String) -> Result<u32, CacheDbError> {
             match self.chars().count().try_into() {
                 Ok(size) => {
-                    return Ok(size);
+                    Ok(size)
                 }
                 Err(_) => {
-                    return Err(CacheDbError::ProtocolSizeBufferOverflow);
+                    Err(CacheDbError::ProtocolSizeBufferOverflow)
                 }
             }
         }
@@ -600,8 +3509,8 @@ mod tests {
         }
     }
 
-    fn basic_client_test() {
-        let cache_client = CacheClient::<String, String>::create_connect([127, 0, 0, 1], 8080).unwrap();
+    fn basic_client_test(port: u16) {
+        let cache_client = CacheClient::<String, String>::create_connect([127, 0, 0, 1], port).unwrap();
         CacheClient::<String, String>::cache_client_handler(&cache_client);
         // .join().unwrap();
 
@@ -613,18 +3522,54 @@ mod tests {
 
     #[test]
     fn basic_server_test() {
-        let cache = CacheDb::<String, String>::new([127, 0, 0, 1], 8080);
-        CacheDb::<String, String>::cache_db_server(&cache);
-        // .join().unwrap();
+        let cache = CacheDb::<String, String>::new([127, 0, 0, 1], 0);
+        let cache_db_server = CacheDb::<String, String>::cache_db_server(&cache).unwrap();
 
         cache.push(KeyValObj{key: "asd".to_string(), val: "das".to_string()});
-        thread::sleep(time::Duration::from_secs(1));
-        basic_client_test();
+        basic_client_test(cache_db_server.local_addr().unwrap().port());
+        cache_db_server.shutdown().unwrap();
+    }
+
+    #[test]
+    fn server_bind_test() {
+        let cache = CacheDb::<String, String>::new([127, 0, 0, 1], 0);
+        let cache_db_server = CacheDb::<String, String>::cache_db_server(&cache).unwrap();
+        let port = cache_db_server.local_addr().unwrap().port();
+        assert_ne!(port, 0);
+
+        // startup errors are returned by cache_db_server
+        let taken_cache = CacheDb::<String, String>::new([127, 0, 0, 1], port);
+        assert_eq!(io::ErrorKind::AddrInUse, CacheDb::<String, String>::cache_db_server(&taken_cache).err().unwrap().kind());
+        cache_db_server.shutdown().unwrap();
+    }
+
+    #[test]
+    fn server_multi_addr_test() {
+        let addrs = [SocketAddr::from(([127, 0, 0, 1], 0)), SocketAddr::from((Ipv6Addr::LOCALHOST, 0))];
+        let cache = CacheDb::<String, String>::new_with_addrs(&addrs[..], CacheDbConfig::default()).unwrap();
+        let cache_db_server = CacheDb::<String, String>::cache_db_server(&cache).unwrap();
+        assert_eq!(cache_db_server.local_addrs().len(), 2);
+        assert!(cache_db_server.local_addrs()[1].is_ipv6());
+
+        // every address is served by the same CacheDb
+        cache.push(KeyValObj{key: "brian".to_string(), val: "test".to_string()});
+        for addr in cache_db_server.local_addrs() {
+            let cache_client = CacheClient::<String, String>::connect(addr).unwrap();
+            let _s = CacheClient::<String, String>::cache_client_handler(&cache_client);
+            let mut res = KeyValObj{key: "brian".to_string(), val: String::new()};
+            cache_client.pull(&"brian".to_string(), &mut res).unwrap();
+            assert_eq!(res.val, "test");
+        }
+        cache_db_server.shutdown().unwrap();
+
+        let no_addrs: &[SocketAddr] = &[];
+        assert!(CacheDb::<String, String>::new_with_addrs(no_addrs, CacheDbConfig::default()).is_err());
     }
 
     #[test]
+    #[allow(clippy::redundant_pattern_matching, clippy::assertions_on_constants)]
     fn local_cache_db_test() {
-        let cache = CacheDb::<String, String>::new([127, 0, 0, 1], 8080);
+        let cache = CacheDb::<String, String>::new([127, 0, 0, 1], 0);
 
         cache.push(KeyValObj {
             key: String::from("brian"),
@@ -672,4 +3617,336 @@ mod tests {
         assert_eq!(&get_res.val, "mod_test");
         println!("mod get k: {} v: {}", get_res.key, get_res.val);
     }
+
+    #[test]
+    fn remove_test() {
+        let cache = CacheDb::<String, String>::new([127, 0, 0, 1], 0);
+
+        cache.push(KeyValObj{key: "brian".to_string(), val: "test".to_string()});
+        assert!(cache.remove(&"brian".to_string()));
+        assert!(cache.get(&"brian".to_string()).is_none());
+        assert!(!cache.remove(&"brian".to_string()));
+    }
+
+    #[test]
+    fn ttl_test() {
+        let cache = CacheDb::<String, String>::new([127, 0, 0, 1], 0);
+
+        cache.push_with_ttl(KeyValObj{key: "brian".to_string(), val: "test".to_string()}, time::Duration::from_millis(50));
+        cache.push(KeyValObj{key: "paul".to_string(), val: "test".to_string()});
+        assert!(cache.ttl(&"brian".to_string()).unwrap().unwrap() <= time::Duration::from_millis(50));
+        assert_eq!(cache.ttl(&"paul".to_string()), Ok(None));
+        assert_eq!(&cache.get(&"brian".to_string()).unwrap().val, "test");
+
+        thread::sleep(time::Duration::from_millis(60));
+        assert!(cache.get(&"brian".to_string()).is_none());
+        assert_eq!(cache.ttl(&"brian".to_string()), Err(CacheDbError::KeyNotFound));
+        assert!(cache.push_with_mode(KeyValObj{key: "brian".to_string(), val: "test1".to_string()}, PushMode::InsertOnly, Some(time::Duration::from_millis(50))));
+
+        thread::sleep(time::Duration::from_millis(60));
+        assert_eq!(cache.stats().entries, 2);
+        cache.sweep_expired();
+        assert_eq!(cache.stats().entries, 1);
+        assert_eq!(&cache.get(&"paul".to_string()).unwrap().val, "test");
+    }
+
+    fn bounded_cache(max_entries: Option<usize>, max_bytes: Option<usize>, eviction_policy: fn() -> Box<dyn EvictionPolicy<String>>, shard_count: usize) -> Arc<CacheDb<String, String>> {
+        CacheDb::<String, String>::new_with_config([127, 0, 0, 1], 0, CacheDbConfig{max_entries, max_bytes, eviction_policy, shard_count, ..Default::default()})
+    }
+
+    #[test]
+    fn lru_eviction_test() {
+        // the policy picks the victims per shard
+        let cache = bounded_cache(Some(2), None, || Box::new(LruPolicy::new()), 1);
+
+        cache.push(KeyValObj{key: "brian".to_string(), val: "test".to_string()});
+        cache.push(KeyValObj{key: "paul".to_string(), val: "test".to_string()});
+        cache.get(&"brian".to_string()).unwrap();
+        cache.push(KeyValObj{key: "pete".to_string(), val: "test".to_string()});
+
+        assert!(cache.get(&"paul".to_string()).is_none());
+        assert!(cache.get(&"brian".to_string()).is_some());
+        assert!(cache.get(&"pete".to_string()).is_some());
+        assert_eq!(cache.stats(), CacheDbStats{entries: 2, bytes: 17, evictions: 1});
+    }
+
+    #[test]
+    fn lfu_eviction_test() {
+        let cache = bounded_cache(Some(2), None, || Box::new(LfuPolicy::new()), 1);
+
+        cache.push(KeyValObj{key: "brian".to_string(), val: "test".to_string()});
+        cache.push(KeyValObj{key: "paul".to_string(), val: "test".to_string()});
+        cache.get(&"brian".to_string()).unwrap();
+        cache.get(&"brian".to_string()).unwrap();
+        cache.get(&"paul".to_string()).unwrap();
+        cache.push(KeyValObj{key: "pete".to_string(), val: "test".to_string()});
+
+        assert!(cache.get(&"paul".to_string()).is_none());
+        assert!(cache.get(&"brian".to_string()).is_some());
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[test]
+    fn random_eviction_byte_budget_test() {
+        // every key val pair is 8 bytes
+        let cache = bounded_cache(None, Some(40), || Box::new(RandomPolicy::with_seed(42)), CACHE_DB_DEFAULT_SHARD_COUNT);
+
+        for i in 0..20 {
+            cache.push(KeyValObj{key: format!("key{:02}", i), val: "val".to_string()});
+        }
+        assert!(cache.get(&"key19".to_string()).is_some());
+        assert_eq!(cache.stats(), CacheDbStats{entries: 5, bytes: 40, evictions: 15});
+
+        cache.remove(&"key19".to_string());
+        assert_eq!(cache.stats(), CacheDbStats{entries: 4, bytes: 32, evictions: 15});
+    }
+
+    #[test]
+    fn sharded_limits_test() {
+        // the limits hold for the whole cache, not for every shard
+        let cache = bounded_cache(Some(10), None, || Box::new(LruPolicy::new()), CACHE_DB_DEFAULT_SHARD_COUNT);
+        for i in 0..100 {
+            cache.push(KeyValObj{key: format!("key{:02}", i), val: "val".to_string()});
+        }
+        assert_eq!(cache.stats(), CacheDbStats{entries: 10, bytes: 80, evictions: 90});
+        assert!(cache.get(&"key99".to_string()).is_some());
+
+        let cache = bounded_cache(None, Some(40), || Box::new(RandomPolicy::with_seed(42)), CACHE_DB_DEFAULT_SHARD_COUNT);
+        cache.push_many((0..5).map(|i| KeyValObj{key: format!("key{:02}", i), val: "val".to_string()}).collect(), None);
+        // none of the keys of a batch are evicted by the batch itself
+        let batch_keys: Vec<String> = (5..9).map(|i| format!("key{:02}", i)).collect();
+        cache.push_many(batch_keys.iter().map(|key| KeyValObj{key: key.clone(), val: "val".to_string()}).collect(), None);
+        assert_eq!(cache.stats(), CacheDbStats{entries: 5, bytes: 40, evictions: 4});
+        assert!(cache.get_many(&batch_keys).iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn sharded_concurrent_push_test() {
+        let config = CacheDbConfig{shard_count: 8, ..Default::default()};
+        let cache = CacheDb::<String, String>::new_with_config([127, 0, 0, 1], 0, config);
+
+        let pushers: Vec<_> = (0..8).map(|t| {
+            let cache = Arc::clone(&cache);
+            thread::spawn(move || {
+                for i in 0..1000 {
+                    cache.push(KeyValObj{key: format!("key{}_{}", t, i), val: format!("val{}", i)});
+                }
+            })
+        }).collect();
+        for pusher in pushers {
+            pusher.join().unwrap();
+        }
+
+        assert_eq!(cache.stats().entries, 8000);
+        for t in 0..8 {
+            assert_eq!(cache.get(&format!("key{}_{}", t, 999)).unwrap().val, "val999");
+        }
+    }
+
+    #[test]
+    fn parse_split_large_frames_test() {
+        let obj = KeyValObj{key: "brian".to_string(), val: "x".repeat(100_000)};
+        assert_eq!(CacheProtocol::<String, String>::assemble_buff(FrameMode::Standard, ProtOpCode::PushOp, 1, &obj, None), Err(CacheDbError::ProtocolSizeBufferOverflow));
+
+        let mut frames = CacheProtocol::<String, String>::assemble_buff(FrameMode::Large, ProtOpCode::PushOp, 1, &obj, Some(time::Duration::from_secs(5))).unwrap();
+        frames.append(&mut CacheProtocol::<String, String>::assemble_buff(FrameMode::Large, ProtOpCode::PullOp, 2, &KeyValObj{key: "paul".to_string(), val: String::new()}, None).unwrap());
+
+        let mut parser = CacheProtocol::<String, String>::with_frame_mode(FrameMode::Large, 1024 * 1024);
+        let mut parsed_op_code = ProtOpCode::TerminateConn;
+        let mut parsed_obj = KeyValObj{key: String::new(), val: String::new()};
+        let mut parsed_frames = Vec::new();
+        for chunk in frames.chunks(1000) {
+            parser.feed(chunk);
+            while parser.parse_buff(&mut parsed_op_code, &mut parsed_obj).unwrap() {
+                parsed_frames.push((parsed_op_code, parser.parsed_req_id(), parsed_obj.key.clone(), parsed_obj.val.len(), parser.parsed_ttl()));
+            }
+        }
+        assert_eq!(parsed_frames, vec![
+            (ProtOpCode::PushOp, 1, "brian".to_string(), 100_000, Some(time::Duration::from_secs(5))),
+            (ProtOpCode::PullOp, 2, "paul".to_string(), 0, None),
+        ]);
+
+        let mut parser = CacheProtocol::<String, String>::with_frame_mode(FrameMode::Large, 1000);
+        parser.feed(&frames[..100]);
+        assert_eq!(parser.parse_buff(&mut parsed_op_code, &mut parsed_obj), Err(CacheDbError::ProtocolSizeBufferOverflow));
+    }
+
+    #[test]
+    fn parse_error_reply_test() {
+        let mut buff = CacheProtocol::<String, String>::assemble_error_reply(FrameMode::Standard, 3, &CacheDbError::DecodingErr, "").unwrap();
+        buff.extend(CacheProtocol::<String, String>::assemble_error_reply(FrameMode::Standard, 4, &CacheDbError::ServerError{code: 42, message: String::new()}, "out of memory").unwrap());
+
+        let mut parser = CacheProtocol::<String, String>::new();
+        parser.feed(&buff);
+        let mut parsed_op_code = ProtOpCode::PullOp;
+        let mut parsed_obj = KeyValObj{key: String::new(), val: String::new()};
+        assert!(parser.parse_buff(&mut parsed_op_code, &mut parsed_obj).unwrap());
+        assert_eq!((parsed_op_code, parser.parsed_req_id(), parser.parsed_error()), (ProtOpCode::ErrorReplyOp, 3, Some(CacheDbError::DecodingErr)));
+        // codes without a variant of their own keep the message
+        assert!(parser.parse_buff(&mut parsed_op_code, &mut parsed_obj).unwrap());
+        assert_eq!(parser.parsed_error(), Some(CacheDbError::ServerError{code: 42, message: "out of memory".to_string()}));
+        assert!(!parser.parse_buff(&mut parsed_op_code, &mut parsed_obj).unwrap());
+    }
+
+    #[test]
+    fn push_mode_test() {
+        let cache = CacheDb::<String, String>::new([127, 0, 0, 1], 0);
+
+        cache.push(KeyValObj{key: "brian".to_string(), val: "test".to_string()});
+        cache.push(KeyValObj{key: "brian".to_string(), val: "test1".to_string()});
+        assert_eq!(&cache.get(&"brian".to_string()).unwrap().val, "test1");
+
+        assert!(!cache.push_with_mode(KeyValObj{key: "brian".to_string(), val: "test2".to_string()}, PushMode::InsertOnly, None));
+        assert_eq!(&cache.get(&"brian".to_string()).unwrap().val, "test1");
+        assert!(cache.push_with_mode(KeyValObj{key: "brian".to_string(), val: "test3".to_string()}, PushMode::UpdateOnly, None));
+        assert_eq!(&cache.get(&"brian".to_string()).unwrap().val, "test3");
+
+        assert!(!cache.push_with_mode(KeyValObj{key: "paul".to_string(), val: "test".to_string()}, PushMode::UpdateOnly, None));
+        assert!(cache.get(&"paul".to_string()).is_none());
+        assert!(cache.push_with_mode(KeyValObj{key: "paul".to_string(), val: "test".to_string()}, PushMode::InsertOnly, None));
+        assert_eq!(&cache.get(&"paul".to_string()).unwrap().val, "test");
+    }
+
+    #[test]
+    fn multi_test() {
+        let cache = CacheDb::<String, String>::new([127, 0, 0, 1], 0);
+
+        let objs: Vec<KeyValObj<String, String>> = (0..100).map(|i| KeyValObj{key: format!("key{}", i), val: format!("val{}", i)}).collect();
+        cache.push_many(objs, Some(Duration::from_secs(60)));
+        assert_eq!(cache.stats().entries, 100);
+        assert!(cache.ttl(&"key42".to_string()).unwrap().is_some());
+
+        let keys = vec!["key99".to_string(), "missing".to_string(), "key0".to_string()];
+        let vals: Vec<Option<String>> = cache.get_many(&keys).into_iter().map(|obj| obj.map(|obj| obj.val)).collect();
+        assert_eq!(vals, vec![Some("val99".to_string()), None, Some("val0".to_string())]);
+    }
+
+    #[test]
+    fn reconnect_backoff_test() {
+        let policy = ReconnectPolicy{max_retries: 10, initial_backoff: Duration::from_millis(100), max_backoff: Duration::from_secs(1), jitter: 0.5};
+        assert_eq!(policy.backoff(1, 0.0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2, 0.0), Duration::from_millis(200));
+        assert_eq!(policy.backoff(4, 0.0), Duration::from_millis(800));
+        assert_eq!(policy.backoff(5, 0.0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(u32::MAX, 0.0), Duration::from_secs(1));
+        // at most half of the backoff is subtracted
+        assert_eq!(policy.backoff(1, 0.5), Duration::from_millis(75));
+        assert!(policy.backoff(2, 0.999) > Duration::from_millis(100));
+    }
+
+    #[test]
+    fn reply_waker_test() {
+        let reply_sync = ReplySync::<u32>::new();
+        let mut cx = Context::from_waker(Waker::noop());
+        let (mut first_waker_index, mut second_waker_index) = (None, None);
+        for _ in 0..10 {
+            assert!(reply_sync.poll_shared_reply(&mut cx, &mut first_waker_index).is_pending());
+            assert!(reply_sync.poll_shared_reply(&mut cx, &mut second_waker_index).is_pending());
+        }
+        // every future keeps a single waker, no matter how often it is polled
+        assert_eq!(reply_sync.reply_wakers.lock().unwrap().len(), 2);
+
+        reply_sync.set_reply(7);
+        assert!(reply_sync.reply_wakers.lock().unwrap().is_empty());
+        assert_eq!(reply_sync.poll_shared_reply(&mut cx, &mut first_waker_index), Poll::Ready(7));
+        assert_eq!(reply_sync.poll_reply(&mut cx, &mut second_waker_index), Poll::Ready(7));
+    }
+
+    #[test]
+    fn hash_ring_test() {
+        let nodes: Vec<SocketAddr> = (0..4).map(|i| SocketAddr::from(([127, 0, 0, 1], 8000 + i))).collect();
+        let mut ring = HashRing::new(160);
+        assert_eq!(ring.node(b"key"), None);
+        for node in nodes.iter().take(3) {
+            ring.add(*node);
+        }
+        let keys: Vec<String> = (0..1000).map(|i| format!("key{}", i)).collect();
+        let before: Vec<SocketAddr> = keys.iter().map(|key| ring.node(key.as_bytes()).unwrap()).collect();
+        for node in nodes.iter().take(3) {
+            assert!(before.iter().filter(|key_node| *key_node == node).count() > 200);
+        }
+
+        // only keys that now belong to the new node move
+        ring.add(nodes[3]);
+        let after: Vec<SocketAddr> = keys.iter().map(|key| ring.node(key.as_bytes()).unwrap()).collect();
+        let moved: Vec<usize> = (0..keys.len()).filter(|i| before[*i] != after[*i]).collect();
+        assert!(moved.iter().all(|i| after[*i] == nodes[3]));
+        assert!(moved.len() > 150 && moved.len() < 350);
+
+        ring.remove(&nodes[3]);
+        assert_eq!(keys.iter().map(|key| ring.node(key.as_bytes()).unwrap()).collect::<Vec<SocketAddr>>(), before);
+    }
+
+    #[test]
+    fn near_cache_test() {
+        let now = Instant::now();
+        let mut near_cache = NearCache::<String, String>::new(NearCacheConfig{max_entries: 2, ttl: Duration::from_secs(1)});
+        near_cache.insert("brian".to_string(), "test".to_string(), now);
+        near_cache.insert("paul".to_string(), "test1".to_string(), now);
+        assert_eq!(near_cache.get(&"brian".to_string(), now), Some("test".to_string()));
+
+        // paul is the least recently used entry
+        near_cache.insert("mia".to_string(), "test2".to_string(), now);
+        assert_eq!(near_cache.get(&"paul".to_string(), now), None);
+        assert_eq!(near_cache.stats(), NearCacheStats{entries: 2, hits: 1, misses: 1, invalidations: 0});
+
+        // expired entries are dropped once they are read
+        assert_eq!(near_cache.get(&"mia".to_string(), now + Duration::from_secs(1)), None);
+        assert_eq!(near_cache.stats().entries, 1);
+        assert!(near_cache.remove(&"brian".to_string()));
+        assert!(!near_cache.remove(&"brian".to_string()));
+    }
+
+    #[test]
+    fn outbound_thread_test() {
+        let cache = CacheDb::<String, String>::new([127, 0, 0, 1], 0);
+        let cache_db_server = CacheDb::<String, String>::cache_db_server(&cache).unwrap();
+        let cache_client = CacheClient::<String, String>::create_connect([127, 0, 0, 1], cache_db_server.local_addr().unwrap().port()).unwrap();
+        CacheClient::<String, String>::cache_client_handler(&cache_client);
+        let outbound_started = || cache.tracking.lock().unwrap().conns.values().map(|conn| conn.outbound.is_some()).collect::<Vec<bool>>();
+
+        // connections that neither track keys nor watch do not start an outbound thread
+        cache.push(KeyValObj{key: "brian".to_string(), val: "test".to_string()});
+        let mut test_res = KeyValObj{key: String::new(), val: String::new()};
+        cache_client.pull(&"brian".to_string(), &mut test_res).unwrap();
+        assert_eq!(outbound_started(), vec![false]);
+
+        cache_client.watch(WatchTarget::Key("brian".to_string())).unwrap();
+        assert_eq!(outbound_started(), vec![true]);
+        cache_db_server.shutdown().unwrap();
+    }
+
+    #[test]
+    fn parse_multi_test() {
+        let entries = vec![(KeyValObj{key: "brian".to_string(), val: "test".to_string()}, true), (KeyValObj{key: "paul".to_string(), val: String::new()}, false)];
+        let mut buff = CacheProtocol::<String, String>::assemble_multi_buff(FrameMode::Standard, ProtOpCode::MultiPullReplyOp, 5, &entries, None).unwrap();
+        buff.extend(CacheProtocol::<String, String>::assemble_multi_buff(FrameMode::Standard, ProtOpCode::MultiPushOp, 6, &entries, Some(Duration::from_secs(1))).unwrap());
+
+        let mut parser = CacheProtocol::<String, String>::new();
+        parser.feed(&buff);
+        let mut parsed_op_code = ProtOpCode::PullOp;
+        let mut parsed_obj = KeyValObj{key: String::new(), val: String::new()};
+        assert!(parser.parse_buff(&mut parsed_op_code, &mut parsed_obj).unwrap());
+        assert_eq!((parsed_op_code, parser.parsed_req_id()), (ProtOpCode::MultiPullReplyOp, 5));
+        let parsed_entries: Vec<(String, String, bool)> = parser.take_parsed_entries().into_iter().map(|(obj, found)| (obj.key, obj.val, found)).collect();
+        assert_eq!(parsed_entries, vec![("brian".to_string(), "test".to_string(), true), ("paul".to_string(), String::new(), false)]);
+        // pushed entries are always found
+        assert!(parser.parse_buff(&mut parsed_op_code, &mut parsed_obj).unwrap());
+        assert_eq!((parsed_op_code, parser.parsed_req_id(), parser.parsed_ttl()), (ProtOpCode::MultiPushOp, 6, Some(Duration::from_secs(1))));
+        let parsed_entries = parser.take_parsed_entries();
+        assert!(parsed_entries.iter().all(|(_, found)| *found));
+        assert_eq!(parsed_entries[0].0.val, "test");
+        assert!(!parser.parse_buff(&mut parsed_op_code, &mut parsed_obj).unwrap());
+
+        // entries that have been scanned are kept while the rest of the frame is fed byte by byte
+        let mut parsed_req_ids = Vec::new();
+        for byte in buff.iter() {
+            parser.feed(std::slice::from_ref(byte));
+            while parser.parse_buff(&mut parsed_op_code, &mut parsed_obj).unwrap() {
+                parsed_req_ids.push(parser.parsed_req_id());
+                assert_eq!(parser.take_parsed_entries().len(), 2);
+            }
+        }
+        assert_eq!(parsed_req_ids, vec![5, 6]);
+    }
 }
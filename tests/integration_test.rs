@@ -1,25 +1,31 @@
+use std::io;
+use std::io::prelude::*;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::thread;
 use std::time;
-use rustcachedb::{CacheDb, CacheClient, CacheDbError, KeyValObj};
+use rustcachedb::{CacheDb, CacheDbConfig, CacheClient, CacheClientConfig, CacheClientPool, CacheClientPoolConfig, CacheClusterClient, CacheClusterConfig, CacheDbError, CacheProtocol, ConnAddr, ConnCloseReason, ConnState, FrameMode, KeyValObj, NearCacheConfig, ProtHello, ProtOpCode, PoolDispatch, PushMode, ReconnectPolicy, ServerEvent, WatchEvent, WatchTarget, WriteConcern};
+use rustcachedb::{block_on, block_on_with_deadline, CACHE_PROTOCOL_VERSION, CACHE_PROTOCOL_CAP_LARGE_FRAMES, CACHE_PROTOCOL_CAP_TTL, CACHE_PROTOCOL_CAP_INVALIDATION};
 
-#[derive(Clone, Default, Debug, PartialEq)]
+#[derive(Clone, Default, Debug, PartialEq, Eq, Hash)]
 struct CacheString(String);
 
 impl rustcachedb::GenericKeyVal<CacheString> for CacheString {
-    fn get_size(self: &Self) -> Result<u16, CacheDbError> {
+    fn get_size(&self) -> Result<u32, CacheDbError> {
         match self.0.chars().count().try_into() {
             Ok(size) => {
-                return Ok(size);
+                Ok(size)
             }
             Err(_) => {
-                return Err(CacheDbError::ProtocolSizeBufferOverflow);
+                Err(CacheDbError::ProtocolSizeBufferOverflow)
             }
         }
     }
 
     // must clone string since into_bytes() is not implemented for
     // the string reference (at least not the copy trait which is required)
-    fn get_bytes(self: &Self) -> Vec<u8> {
+    fn get_bytes(&self) -> Vec<u8> {
         let str_bytes = self.clone();
         str_bytes.0.into_bytes()
     }
@@ -32,14 +38,14 @@ impl rustcachedb::GenericKeyVal<CacheString> for CacheString {
     }
 }
 
-fn client_test_multiple_keys() {
-    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], 8081).unwrap();
+fn client_test_multiple_keys(port: u16) {
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], port).unwrap();
     let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
         // .join().unwrap();
     for i in 1..10 {
         let co = KeyValObj {
-            key: CacheString(String::from(format!("key{}", i))),
-            val: CacheString(String::from(format!("val{}", i))),
+            key: CacheString(format!("key{}", i)),
+            val: CacheString(format!("val{}", i)),
         };
         // println!("test push {:?}", i);
         cache_client.push(co).unwrap();
@@ -50,8 +56,8 @@ fn client_test_multiple_keys() {
     let mut get_res = KeyValObj{key: CacheString(String::new()), val: CacheString(String::new())};
     for i in 1..10 {
         // pull_k = CacheString(String::from("key2"));
-        pull_k = CacheString(String::from(format!("key{}", i)));
-        pull_v = String::from(format!("val{}", i));
+        pull_k = CacheString(format!("key{}", i));
+        pull_v = format!("val{}", i);
         cache_client.pull(&pull_k, &mut get_res).unwrap();
         // println!("received: {:}, {:}", get_res.key.0, get_res.val.0);
         assert_eq!(get_res.val.0, pull_v);
@@ -61,8 +67,8 @@ fn client_test_multiple_keys() {
     // }
 }
 
-fn client_test_single_key() {
-    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], 8081).unwrap();
+fn client_test_single_key(port: u16) {
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], port).unwrap();
     let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
         // .join().unwrap();
     
@@ -100,8 +106,8 @@ fn client_test_single_key() {
     // }
 }
 
-fn client_test_single_key_async() {
-    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], 8081).unwrap();
+fn client_test_single_key_async(port: u16) {
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], port).unwrap();
     let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
         // .join().unwrap();
     
@@ -115,33 +121,998 @@ fn client_test_single_key_async() {
     let pull_k = CacheString(String::from("key2"));
 
     let mut i = 0;
+    let mut get_futures = Vec::new();
     for _ in 1..500 {
-        let _get_res = CacheClient::pull_async(&cache_client, &pull_k);
+        get_futures.push(CacheClient::pull_async(&cache_client, &pull_k));
         i += 1;
-        // join thread and wait
     }
     assert_eq!(499, i);
+    // the futures are resolved by the cache_client_handler, no thread is spawned per pull
+    let mut res = KeyValObj{key: pull_k.clone(), val: CacheString::default()};
+    let pull_res = cache_client.pull(&pull_k, &mut res).map(|_| res.val);
+    for get_future in get_futures {
+        assert_eq!(pull_res, block_on(get_future).map(|res| res.val));
+    }
 
     // if let Err(e) = s.join() {
     //     panic!("{:?}", e);
     // }
 }
 
+fn client_test_push_modes(port: u16) {
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], port).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+
+    let key = CacheString("mode_key".to_string());
+    let mut get_res = KeyValObj{key: CacheString(String::new()), val: CacheString(String::new())};
+
+    cache_client.push(KeyValObj{key: key.clone(), val: CacheString("val1".to_string())}).unwrap();
+    cache_client.push(KeyValObj{key: key.clone(), val: CacheString("val2".to_string())}).unwrap();
+    cache_client.pull(&key, &mut get_res).unwrap();
+    assert_eq!(get_res.val.0, "val2");
+
+    cache_client.push_with_mode(KeyValObj{key: key.clone(), val: CacheString("val3".to_string())}, PushMode::InsertOnly, None).unwrap();
+    cache_client.pull(&key, &mut get_res).unwrap();
+    assert_eq!(get_res.val.0, "val2");
+
+    cache_client.push_with_mode(KeyValObj{key: key.clone(), val: CacheString("val4".to_string())}, PushMode::UpdateOnly, None).unwrap();
+    cache_client.pull(&key, &mut get_res).unwrap();
+    assert_eq!(get_res.val.0, "val4");
+
+    let missing_key = CacheString("mode_missing_key".to_string());
+    cache_client.push_with_mode(KeyValObj{key: missing_key.clone(), val: CacheString("val".to_string())}, PushMode::UpdateOnly, None).unwrap();
+    assert_eq!(CacheDbError::KeyNotFound, cache_client.pull(&missing_key, &mut get_res).unwrap_err());
+}
+
+fn client_test_delete(port: u16) {
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], port).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+
+    let key = CacheString("delete_key".to_string());
+    let mut get_res = KeyValObj{key: CacheString(String::new()), val: CacheString(String::new())};
+
+    cache_client.push(KeyValObj{key: key.clone(), val: CacheString("val".to_string())}).unwrap();
+    assert!(cache_client.delete(&key).unwrap());
+    assert_eq!(CacheDbError::KeyNotFound, cache_client.pull(&key, &mut get_res).unwrap_err());
+    assert!(!cache_client.delete(&key).unwrap());
+}
+
+fn client_test_ttl(port: u16) {
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], port).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+
+    let key = CacheString("ttl_key".to_string());
+    let mut get_res = KeyValObj{key: CacheString(String::new()), val: CacheString(String::new())};
+
+    cache_client.push_with_ttl(KeyValObj{key: key.clone(), val: CacheString("val".to_string())}, time::Duration::from_millis(200)).unwrap();
+    let ttl = cache_client.ttl(&key).unwrap().unwrap();
+    assert!(ttl > time::Duration::ZERO && ttl <= time::Duration::from_millis(200));
+    cache_client.pull(&key, &mut get_res).unwrap();
+    assert_eq!(get_res.val.0, "val");
+
+    thread::sleep(time::Duration::from_millis(250));
+    assert_eq!(CacheDbError::KeyNotFound, cache_client.pull(&key, &mut get_res).unwrap_err());
+    assert_eq!(CacheDbError::KeyNotFound, cache_client.ttl(&key).unwrap_err());
+    assert_eq!(Ok(None), cache_client.ttl(&CacheString("brian".to_string())));
+}
+
+// requests of different threads share one connection and are matched to their replies by request id
+fn client_test_concurrent_requests(port: u16) {
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], port).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+
+    let mut handles = Vec::new();
+    for t in 0..4 {
+        let cache_client = cache_client.clone();
+        handles.push(thread::spawn(move || {
+            for i in 0..25 {
+                let key = CacheString(format!("concurrent_key{}_{}", t, i));
+                let mut get_res = KeyValObj{key: CacheString(String::new()), val: CacheString(String::new())};
+                if i % 2 == 0 {
+                    cache_client.push_with_ttl(KeyValObj{key: key.clone(), val: CacheString(format!("val{}", i))}, time::Duration::from_secs(60)).unwrap();
+                    assert!(cache_client.ttl(&key).unwrap().is_some());
+                    cache_client.pull(&key, &mut get_res).unwrap();
+                    assert_eq!(get_res.val.0, format!("val{}", i));
+                    assert!(cache_client.delete(&key).unwrap());
+                } else {
+                    assert_eq!(CacheDbError::KeyNotFound, cache_client.ttl(&key).unwrap_err());
+                    assert!(!cache_client.delete(&key).unwrap());
+                }
+            }
+        }));
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
 #[test]
 fn extended_server_test() {
-    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 8081);
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 0);
 
     cache.push(KeyValObj {
         key: CacheString(String::from("brian")),
         val: CacheString(String::from("test")),
     });
 
-    let _cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache);
-        // .join().unwrap();
-        
-    thread::sleep(time::Duration::from_secs(1));
-    client_test_multiple_keys();
-    client_test_single_key();
-    client_test_single_key_async();
-    // cache_db_server.join().unwrap();
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let port = cache_db_server.local_addr().unwrap().port();
+    client_test_multiple_keys(port);
+    client_test_single_key(port);
+    client_test_single_key_async(port);
+    client_test_push_modes(port);
+    client_test_delete(port);
+    client_test_ttl(port);
+    client_test_concurrent_requests(port);
+    cache_db_server.shutdown().unwrap();
+}
+
+#[test]
+fn large_frame_server_test() {
+    let config = CacheDbConfig{frame_mode: FrameMode::Large, ..Default::default()};
+    let cache = CacheDb::<CacheString, CacheString>::new_with_config([127, 0, 0, 1], 0, config);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let port = cache_db_server.local_addr().unwrap().port();
+
+    let client_config = CacheClientConfig{frame_mode: FrameMode::Large, ..Default::default()};
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect_with_config([127, 0, 0, 1], port, client_config).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+
+    // 4 MiB document
+    let key = CacheString("document".to_string());
+    let val = CacheString("x".repeat(4 * 1024 * 1024));
+    cache_client.push(KeyValObj{key: key.clone(), val: val.clone()}).unwrap();
+
+    let mut get_res = KeyValObj{key: CacheString(String::new()), val: CacheString(String::new())};
+    cache_client.pull(&key, &mut get_res).unwrap();
+    assert_eq!(get_res.val, val);
+}
+
+#[test]
+fn handshake_test() {
+    let config = CacheDbConfig{frame_mode: FrameMode::Large, server_identity: "handshake-test-server".to_string(), handshake_timeout: time::Duration::from_millis(200), ..Default::default()};
+    let cache = CacheDb::<CacheString, CacheString>::new_with_config([127, 0, 0, 1], 0, config);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let port = cache_db_server.local_addr().unwrap().port();
+
+    let client_config = CacheClientConfig{frame_mode: FrameMode::Large, ..Default::default()};
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect_with_config([127, 0, 0, 1], port, client_config).unwrap();
+    assert_eq!(cache_client.server_hello().version, CACHE_PROTOCOL_VERSION);
+    assert_eq!(cache_client.server_hello().identity, "handshake-test-server");
+    assert_ne!(cache_client.server_hello().capabilities & CACHE_PROTOCOL_CAP_LARGE_FRAMES, 0);
+
+    // the server uses large frames
+    let res = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], port);
+    assert_eq!(CacheDbError::CapabilityMismatch, res.err().unwrap());
+
+    // the server replies with its own version and closes the connection
+    let mut tcp_stream = TcpStream::connect(cache_db_server.local_addr().unwrap()).unwrap();
+    let hello = ProtHello{version: CACHE_PROTOCOL_VERSION + 1, capabilities: CACHE_PROTOCOL_CAP_LARGE_FRAMES, identity: "future-client".to_string()};
+    tcp_stream.write_all(&hello.assemble_buff(ProtOpCode::HelloOp).unwrap()).unwrap();
+    let server_hello = ProtHello::read_hello(&mut tcp_stream, ProtOpCode::HelloReplyOp).unwrap();
+    assert_eq!(server_hello.version, CACHE_PROTOCOL_VERSION);
+    assert_eq!(tcp_stream.read(&mut [0u8; 1]).unwrap(), 0);
+
+    // a client that never sends its hello is closed once the handshake timed out
+    let mut tcp_stream = TcpStream::connect(cache_db_server.local_addr().unwrap()).unwrap();
+    tcp_stream.set_read_timeout(Some(time::Duration::from_secs(2))).unwrap();
+    assert_eq!(tcp_stream.read(&mut [0u8; 1]).unwrap(), 0);
+}
+
+#[test]
+fn shutdown_test() {
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 0);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let server_addr = cache_db_server.local_addr().unwrap();
+
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], server_addr.port()).unwrap();
+    let cache_client_handler = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+    let key = CacheString("key".to_string());
+    cache_client.push(KeyValObj{key: key.clone(), val: CacheString("val".to_string())}).unwrap();
+    let mut get_res = KeyValObj{key: CacheString(String::new()), val: CacheString(String::new())};
+    cache_client.pull(&key, &mut get_res).unwrap();
+
+    // requests that have been sent before the shutdown are still replied to
+    let mut tcp_stream = TcpStream::connect(server_addr).unwrap();
+    let hello = ProtHello{version: CACHE_PROTOCOL_VERSION, capabilities: 0, identity: "shutdown-test".to_string()};
+    tcp_stream.write_all(&hello.assemble_buff(ProtOpCode::HelloOp).unwrap()).unwrap();
+    ProtHello::read_hello(&mut tcp_stream, ProtOpCode::HelloReplyOp).unwrap();
+    let mut send_buff = Vec::new();
+    for req_id in 1..=100 {
+        let pull_obj = KeyValObj{key: key.clone(), val: CacheString::default()};
+        send_buff.extend(CacheProtocol::assemble_buff(FrameMode::Standard, ProtOpCode::PullOp, req_id, &pull_obj, None).unwrap());
+    }
+    tcp_stream.write_all(&send_buff).unwrap();
+
+    cache_db_server.shutdown().unwrap();
+
+    let mut recv_buff = Vec::new();
+    tcp_stream.read_to_end(&mut recv_buff).unwrap();
+    let mut parser = CacheProtocol::<CacheString, CacheString>::new();
+    parser.feed(&recv_buff);
+    let mut parsed_op_code = ProtOpCode::PullOp;
+    let mut parsed_obj = KeyValObj{key: CacheString::default(), val: CacheString::default()};
+    let mut parsed_ops = Vec::new();
+    while parser.parse_buff(&mut parsed_op_code, &mut parsed_obj).unwrap() {
+        parsed_ops.push((parsed_op_code, parser.parsed_req_id()));
+    }
+    assert_eq!(parsed_ops.len(), 101);
+    assert!(parsed_ops[..100].iter().enumerate().all(|(i, op)| *op == (ProtOpCode::PullReplyOp, i as u32 + 1)));
+    assert_eq!(parsed_ops[100].0, ProtOpCode::TerminateConn);
+
+    assert_eq!(Ok(()), cache_client_handler.join().unwrap());
+    assert_eq!(CacheDbError::NetworkError, CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], server_addr.port()).err().unwrap());
+}
+
+fn raw_connect(server_addr: SocketAddr) -> TcpStream {
+    let mut tcp_stream = TcpStream::connect(server_addr).unwrap();
+    let hello = ProtHello{version: CACHE_PROTOCOL_VERSION, capabilities: 0, identity: "raw-client".to_string()};
+    tcp_stream.write_all(&hello.assemble_buff(ProtOpCode::HelloOp).unwrap()).unwrap();
+    ProtHello::read_hello(&mut tcp_stream, ProtOpCode::HelloReplyOp).unwrap();
+    tcp_stream
+}
+
+// events are emitted by the client handler threads
+fn wait_for_event(events: &Mutex<Vec<ServerEvent>>, event: &ServerEvent) {
+    let deadline = time::Instant::now() + time::Duration::from_secs(5);
+    while !events.lock().unwrap().contains(event) {
+        assert!(time::Instant::now() < deadline, "missing event {:?}", event);
+        thread::sleep(time::Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn server_events_test() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_clone = Arc::clone(&events);
+    let config = CacheDbConfig{
+        server_event_hook: Some(Arc::new(move |event: &ServerEvent| events_clone.lock().unwrap().push(event.clone()))),
+        handshake_timeout: time::Duration::from_millis(500),
+        ..Default::default()
+    };
+    let cache = CacheDb::<CacheString, CacheString>::new_with_config([127, 0, 0, 1], 0, config);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let server_addr = cache_db_server.local_addr().unwrap();
+    cache.push(KeyValObj{key: CacheString("key".to_string()), val: CacheString("val".to_string())});
+
+    let mut tcp_stream = raw_connect(server_addr);
+    let client_addr = ConnAddr::Tcp(tcp_stream.local_addr().unwrap());
+    wait_for_event(&events, &ServerEvent::Connected(client_addr.clone()));
+
+    // a key that is no valid utf-8 can not be decoded into a CacheString, the following frames are still handled
+    let mut send_buff = vec![ProtOpCode::PullOp as u8];
+    send_buff.extend_from_slice(&7u32.to_be_bytes());
+    send_buff.extend_from_slice(&[0, 2, 0xff, 0xfe, 0, 0]);
+    let pull_obj = KeyValObj{key: CacheString("key".to_string()), val: CacheString::default()};
+    send_buff.extend(CacheProtocol::assemble_buff(FrameMode::Standard, ProtOpCode::PullOp, 8, &pull_obj, None).unwrap());
+    // unknown op code
+    send_buff.push(0xff);
+    tcp_stream.write_all(&send_buff).unwrap();
+
+    let mut recv_buff = Vec::new();
+    tcp_stream.read_to_end(&mut recv_buff).unwrap();
+    let mut parser = CacheProtocol::<CacheString, CacheString>::new();
+    parser.feed(&recv_buff);
+    let mut parsed_op_code = ProtOpCode::PullOp;
+    let mut parsed_obj = KeyValObj{key: CacheString::default(), val: CacheString::default()};
+    let mut parsed_ops = Vec::new();
+    while parser.parse_buff(&mut parsed_op_code, &mut parsed_obj).unwrap() {
+        parsed_ops.push((parsed_op_code, parser.parsed_req_id()));
+    }
+    assert_eq!(parsed_ops, vec![(ProtOpCode::ErrorReplyOp, 7), (ProtOpCode::PullReplyOp, 8), (ProtOpCode::ErrorReplyOp, 0)]);
+    wait_for_event(&events, &ServerEvent::FrameError(client_addr.clone(), CacheDbError::DecodingErr));
+    wait_for_event(&events, &ServerEvent::Closed(client_addr.clone(), ConnCloseReason::ProtocolError(CacheDbError::ParsingErr)));
+
+    let tcp_stream = raw_connect(server_addr);
+    let client_addr = ConnAddr::Tcp(tcp_stream.local_addr().unwrap());
+    drop(tcp_stream);
+    wait_for_event(&events, &ServerEvent::Closed(client_addr.clone(), ConnCloseReason::Eof));
+
+    let tcp_stream = TcpStream::connect(server_addr).unwrap();
+    let client_addr = ConnAddr::Tcp(tcp_stream.local_addr().unwrap());
+    drop(tcp_stream);
+    wait_for_event(&events, &ServerEvent::Closed(client_addr.clone(), ConnCloseReason::HandshakeFailed(CacheDbError::NetworkError)));
+
+    // a client that never sends its hello is closed once the handshake timed out
+    let tcp_stream = TcpStream::connect(server_addr).unwrap();
+    let client_addr = ConnAddr::Tcp(tcp_stream.local_addr().unwrap());
+    wait_for_event(&events, &ServerEvent::Closed(client_addr.clone(), ConnCloseReason::HandshakeFailed(CacheDbError::NetworkTimeOutError)));
+    drop(tcp_stream);
+
+    let tcp_stream = raw_connect(server_addr);
+    let client_addr = ConnAddr::Tcp(tcp_stream.local_addr().unwrap());
+    wait_for_event(&events, &ServerEvent::Connected(client_addr.clone()));
+    cache_db_server.shutdown().unwrap();
+    wait_for_event(&events, &ServerEvent::Closed(client_addr.clone(), ConnCloseReason::Shutdown));
+}
+
+#[test]
+fn error_reply_test() {
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 0);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    // too large for a standard frame
+    let key = CacheString("document".to_string());
+    cache.push(KeyValObj{key: key.clone(), val: CacheString("x".repeat(100_000))});
+
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], cache_db_server.local_addr().unwrap().port()).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+
+    let start = time::Instant::now();
+    let mut get_res = KeyValObj{key: CacheString(String::new()), val: CacheString(String::new())};
+    assert_eq!(CacheDbError::ProtocolSizeBufferOverflow, cache_client.pull(&key, &mut get_res).unwrap_err());
+    assert!(start.elapsed() < time::Duration::from_secs(1));
+
+    // the connection stays usable
+    cache_client.push(KeyValObj{key: key.clone(), val: CacheString("x".to_string())}).unwrap();
+    cache_client.pull(&key, &mut get_res).unwrap();
+    assert_eq!(get_res.val.0, "x");
+    cache_db_server.shutdown().unwrap();
+}
+
+#[test]
+fn acked_push_test() {
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 0);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let port = cache_db_server.local_addr().unwrap().port();
+
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], port).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+    let key = CacheString("acked_key".to_string());
+    assert!(cache_client.push_acked(KeyValObj{key: key.clone(), val: CacheString("val1".to_string())}, PushMode::InsertOnly, None).unwrap());
+    // the obj is stored once the ack arrived
+    assert_eq!(cache.get(&key).unwrap().val.0, "val1");
+    assert!(!cache_client.push_acked(KeyValObj{key: key.clone(), val: CacheString("val2".to_string())}, PushMode::InsertOnly, None).unwrap());
+    assert!(!cache_client.push_acked(KeyValObj{key: CacheString("acked_missing_key".to_string()), val: CacheString("val".to_string())}, PushMode::UpdateOnly, None).unwrap());
+    assert!(cache_client.push_acked(KeyValObj{key: key.clone(), val: CacheString("val3".to_string())}, PushMode::Upsert, Some(time::Duration::from_secs(60))).unwrap());
+    assert_eq!(cache.get(&key).unwrap().val.0, "val3");
+    assert!(cache.ttl(&key).unwrap().is_some());
+
+    // every push waits for its ack
+    let client_config = CacheClientConfig{write_concern: WriteConcern::Acknowledged, ..Default::default()};
+    let acked_client = CacheClient::<CacheString, CacheString>::create_connect_with_config([127, 0, 0, 1], port, client_config).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&acked_client);
+    for i in 0..100 {
+        let key = CacheString(format!("acked_key{}", i));
+        acked_client.push(KeyValObj{key: key.clone(), val: CacheString(format!("val{}", i))}).unwrap();
+        assert_eq!(cache.get(&key).unwrap().val.0, format!("val{}", i));
+    }
+    let key = CacheString("acked_key0".to_string());
+    assert_eq!(acked_client.push_with_mode(KeyValObj{key: key.clone(), val: CacheString("val".to_string())}, PushMode::InsertOnly, None), Err(CacheDbError::NotStored));
+    assert_eq!(cache.get(&key).unwrap().val.0, "val0");
+    let missing_key = CacheString("acked_missing_key".to_string());
+    assert_eq!(acked_client.push_with_mode(KeyValObj{key: missing_key.clone(), val: CacheString("val".to_string())}, PushMode::UpdateOnly, None), Err(CacheDbError::NotStored));
+    assert!(cache.get(&missing_key).is_none());
+    cache_db_server.shutdown().unwrap();
+}
+
+#[test]
+fn multi_test() {
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 0);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let port = cache_db_server.local_addr().unwrap().port();
+
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect([127, 0, 0, 1], port).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+    let objs: Vec<KeyValObj<CacheString, CacheString>> = (0..50).map(|i| KeyValObj{key: CacheString(format!("multi_key{}", i)), val: CacheString(format!("val{}", i))}).collect();
+    cache_client.push_many(&objs, None).unwrap();
+
+    let mut keys: Vec<CacheString> = (0..50).rev().map(|i| CacheString(format!("multi_key{}", i))).collect();
+    keys.insert(10, CacheString("multi_missing_key".to_string()));
+    let vals = cache_client.pull_many(&keys).unwrap();
+    assert_eq!(vals.len(), 51);
+    assert!(vals[10].is_none());
+    for (key, val) in keys.iter().zip(vals.iter()) {
+        if key.0 != "multi_missing_key" {
+            assert_eq!(val.as_ref().unwrap().0, key.0.replace("multi_key", "val"));
+        }
+    }
+    assert_eq!(cache_client.pull_many(&[]).unwrap(), Vec::new());
+
+    // acknowledged batches are stored once the call returns
+    let client_config = CacheClientConfig{write_concern: WriteConcern::Acknowledged, ..Default::default()};
+    let acked_client = CacheClient::<CacheString, CacheString>::create_connect_with_config([127, 0, 0, 1], port, client_config).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&acked_client);
+    acked_client.push_many(&[KeyValObj{key: CacheString("multi_key0".to_string()), val: CacheString("updated".to_string())}], Some(time::Duration::from_secs(60))).unwrap();
+    assert_eq!(cache.get(&CacheString("multi_key0".to_string())).unwrap().val.0, "updated");
+    assert!(cache.ttl(&CacheString("multi_key0".to_string())).unwrap().is_some());
+    cache_db_server.shutdown().unwrap();
+}
+
+#[test]
+fn reconnect_test() {
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 0);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let port = cache_db_server.local_addr().unwrap().port();
+
+    let states = Arc::new(Mutex::new(Vec::new()));
+    let states_clone = Arc::clone(&states);
+    let client_config = CacheClientConfig{
+        reconnect_policy: Some(ReconnectPolicy{max_retries: 20, initial_backoff: time::Duration::from_millis(10), max_backoff: time::Duration::from_millis(100), jitter: 0.5}),
+        conn_state_hook: Some(Arc::new(move |state: &ConnState| states_clone.lock().unwrap().push(state.clone()))),
+        ..Default::default()
+    };
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect_with_config([127, 0, 0, 1], port, client_config).unwrap();
+    let client_handler = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+    let key = CacheString("reconnect_key".to_string());
+    cache_client.push_acked(KeyValObj{key: key.clone(), val: CacheString("val".to_string())}, PushMode::Upsert, None).unwrap();
+
+    // the server restarts on the same port
+    cache_db_server.shutdown().unwrap();
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], port);
+    cache.push(KeyValObj{key: key.clone(), val: CacheString("restarted_val".to_string())});
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+
+    // the pull is re-issued if it has been sent before the client reconnected
+    let mut res = KeyValObj{key: key.clone(), val: CacheString::default()};
+    cache_client.pull(&key, &mut res).unwrap();
+    assert_eq!(res.val.0, "restarted_val");
+    assert!(states.lock().unwrap().contains(&ConnState::Reconnected));
+    assert!(matches!(states.lock().unwrap()[0], ConnState::Disconnected(_)));
+
+    // the client gives up once the retry budget is exhausted
+    cache_db_server.shutdown().unwrap();
+    assert_eq!(client_handler.join().unwrap(), Err(CacheDbError::NetworkError));
+    assert_eq!(states.lock().unwrap().last(), Some(&ConnState::Failed(CacheDbError::NetworkError)));
+    assert_eq!(cache_client.pull(&key, &mut res), Err(CacheDbError::NetworkError));
+}
+
+#[test]
+fn client_pool_test() {
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 0);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let port = cache_db_server.local_addr().unwrap().port();
+
+    for dispatch in [PoolDispatch::RoundRobin, PoolDispatch::KeyHash] {
+        let pool_config = CacheClientPoolConfig{size: 4, dispatch, client_config: CacheClientConfig{write_concern: WriteConcern::Acknowledged, ..Default::default()}};
+        let pool = Arc::new(CacheClientPool::<CacheString, CacheString>::create_connect_with_config([127, 0, 0, 1], port, pool_config).unwrap());
+        for i in 0..20 {
+            pool.push(KeyValObj{key: CacheString(format!("pool_key{}", i)), val: CacheString(format!("val{}", i))}).unwrap();
+        }
+
+        let mut pull_threads = Vec::new();
+        for t in 0..8 {
+            let pool = Arc::clone(&pool);
+            pull_threads.push(thread::spawn(move || {
+                for i in 0..100 {
+                    let key = CacheString(format!("pool_key{}", (i + t) % 20));
+                    let mut res = KeyValObj{key: key.clone(), val: CacheString::default()};
+                    pool.pull(&key, &mut res).unwrap();
+                    assert_eq!(res.val.0, key.0.replace("pool_key", "val"));
+                }
+            }));
+        }
+        for pull_thread in pull_threads {
+            pull_thread.join().unwrap();
+        }
+        assert!(pool.delete(&CacheString("pool_key0".to_string())).unwrap());
+        let mut res = KeyValObj{key: CacheString("pool_key0".to_string()), val: CacheString::default()};
+        assert_eq!(pool.pull(&CacheString("pool_key0".to_string()), &mut res), Err(CacheDbError::KeyNotFound));
+
+        let stats = pool.stats();
+        assert_eq!((stats.size, stats.connected, stats.pulls_in_flight), (4, 4, 0));
+    }
+
+    let pool = CacheClientPool::<CacheString, CacheString>::create_connect([127, 0, 0, 1], port, 2).unwrap();
+    cache_db_server.shutdown().unwrap();
+    // the server terminates all connections of the pool
+    assert_eq!(pool.stats().size, 2);
+    let handler_results = pool.join();
+    assert!(handler_results.iter().all(|res| res.is_ok()));
+}
+
+#[test]
+fn cluster_client_test() {
+    // open connections of all nodes
+    let conns = Arc::new(AtomicI64::new(0));
+    let caches: Vec<Arc<CacheDb<CacheString, CacheString>>> = (0..3).map(|_| {
+        let conns = Arc::clone(&conns);
+        let server_event_hook = move |event: &ServerEvent| match event {
+            ServerEvent::Connected(_) => { conns.fetch_add(1, Ordering::SeqCst); }
+            ServerEvent::Closed(..) => { conns.fetch_sub(1, Ordering::SeqCst); }
+            _ => {}
+        };
+        CacheDb::<CacheString, CacheString>::new_with_config([127, 0, 0, 1], 0, CacheDbConfig{server_event_hook: Some(Arc::new(server_event_hook)), ..Default::default()})
+    }).collect();
+    let cache_db_servers: Vec<_> = caches.iter().map(|cache| CacheDb::<CacheString, CacheString>::cache_db_server(cache).unwrap()).collect();
+    let nodes: Vec<([u8; 4], u16)> = cache_db_servers.iter().map(|server| ([127, 0, 0, 1], server.local_addr().unwrap().port())).collect();
+
+    let client_config = CacheClientConfig{write_concern: WriteConcern::Acknowledged, ..Default::default()};
+    let cluster_config = CacheClusterConfig{client_config, ..Default::default()};
+    let cluster_client = CacheClusterClient::<CacheString, CacheString>::create_connect_with_config(&nodes[..2], cluster_config).unwrap();
+    for i in 0..100 {
+        cluster_client.push(KeyValObj{key: CacheString(format!("cluster_key{}", i)), val: CacheString(format!("val{}", i))}).unwrap();
+    }
+    // the keys are spread across the nodes
+    assert!(caches[0].stats().entries > 0 && caches[1].stats().entries > 0);
+    assert_eq!(caches[0].stats().entries + caches[1].stats().entries, 100);
+
+    let keys: Vec<CacheString> = (0..100).map(|i| CacheString(format!("cluster_key{}", i))).collect();
+    let vals = cluster_client.pull_many(&keys).unwrap();
+    assert!(vals.iter().enumerate().all(|(i, val)| val.as_ref().unwrap().0 == format!("val{}", i)));
+
+    // the new node only takes over keys, so all keys that did not move can still be pulled
+    cluster_client.add_node(nodes[2].0, nodes[2].1).unwrap();
+    assert_eq!(cluster_client.nodes().len(), 3);
+    let vals = cluster_client.pull_many(&keys).unwrap();
+    let moved = vals.iter().filter(|val| val.is_none()).count();
+    assert!(moved > 0 && moved < 60);
+    cluster_client.push_many(&keys.iter().map(|key| KeyValObj{key: key.clone(), val: CacheString("new_val".to_string())}).collect::<Vec<_>>(), None).unwrap();
+    assert_eq!(caches[2].stats().entries, moved);
+
+    // the keys of a removed node are routed to the remaining nodes
+    assert!(cluster_client.remove_node(nodes[0].0, nodes[0].1));
+    assert!(!cluster_client.remove_node(nodes[0].0, nodes[0].1));
+    let key = CacheString("cluster_key0".to_string());
+    cluster_client.push(KeyValObj{key: key.clone(), val: CacheString("val".to_string())}).unwrap();
+    let mut res = KeyValObj{key: key.clone(), val: CacheString::default()};
+    cluster_client.pull(&key, &mut res).unwrap();
+    assert_eq!(res.val.0, "val");
+
+    // concurrent adds of the same node keep a single connection to it
+    assert!(cluster_client.remove_node(nodes[2].0, nodes[2].1));
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| cluster_client.add_node(nodes[2].0, nodes[2].1).unwrap());
+        }
+    });
+    assert_eq!(cluster_client.nodes().len(), 2);
+    let deadline = time::Instant::now() + time::Duration::from_secs(5);
+    while conns.load(Ordering::SeqCst) != 2 {
+        assert!(time::Instant::now() < deadline, "{} open connections", conns.load(Ordering::SeqCst));
+        thread::sleep(time::Duration::from_millis(10));
+    }
+
+    for cache_db_server in cache_db_servers {
+        cache_db_server.shutdown().unwrap();
+    }
+}
+
+#[test]
+fn connect_addrs_test() {
+    let cache = CacheDb::<CacheString, CacheString>::new_with_addrs("localhost:0", CacheDbConfig::default()).unwrap();
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let port = cache_db_server.local_addr().unwrap().port();
+    cache.push(KeyValObj{key: CacheString("addr_key".to_string()), val: CacheString("val".to_string())});
+
+    // hostnames are resolved
+    let cache_client = CacheClient::<CacheString, CacheString>::connect(format!("localhost:{}", port)).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+    let mut res = KeyValObj{key: CacheString("addr_key".to_string()), val: CacheString::default()};
+    cache_client.pull(&CacheString("addr_key".to_string()), &mut res).unwrap();
+    assert_eq!(res.val.0, "val");
+
+    // addresses that refuse the connection are skipped
+    let closed_addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let addrs = [closed_addr, cache_db_server.local_addr().unwrap()];
+    let cache_client = CacheClient::<CacheString, CacheString>::connect(&addrs[..]).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+    cache_client.pull(&CacheString("addr_key".to_string()), &mut res).unwrap();
+    assert_eq!(CacheClient::<CacheString, CacheString>::connect(closed_addr).err(), Some(CacheDbError::NetworkError));
+    cache_db_server.shutdown().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket_test() {
+    let path = std::env::temp_dir().join(format!("rustcachedb-test-{}.sock", std::process::id()));
+    // a socket file left behind by a server that did not shut down is replaced
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_clone = Arc::clone(&events);
+    let config = CacheDbConfig{
+        unix_socket_path: Some(path.clone()),
+        server_event_hook: Some(Arc::new(move |event: &ServerEvent| events_clone.lock().unwrap().push(event.clone()))),
+        ..Default::default()
+    };
+    let no_addrs: &[SocketAddr] = &[];
+    let cache = CacheDb::<CacheString, CacheString>::new_with_addrs(no_addrs, config).unwrap();
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    assert_eq!(cache_db_server.unix_socket_path(), Some(path.as_path()));
+    assert_eq!(cache_db_server.local_addr(), None);
+    // the socket of a running server is not replaced
+    let taken_config = CacheDbConfig{unix_socket_path: Some(path.clone()), ..Default::default()};
+    let taken_cache = CacheDb::<CacheString, CacheString>::new_with_addrs(no_addrs, taken_config).unwrap();
+    assert_eq!(io::ErrorKind::AddrInUse, CacheDb::<CacheString, CacheString>::cache_db_server(&taken_cache).err().unwrap().kind());
+
+    let cache_client = CacheClient::<CacheString, CacheString>::connect_unix(&path).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+    cache_client.push(KeyValObj{key: CacheString("unix_key".to_string()), val: CacheString("val".to_string())}).unwrap();
+    let mut res = KeyValObj{key: CacheString("unix_key".to_string()), val: CacheString::default()};
+    cache_client.pull(&CacheString("unix_key".to_string()), &mut res).unwrap();
+    assert_eq!(res.val.0, "val");
+    wait_for_event(&events, &ServerEvent::Connected(ConnAddr::Unix(None)));
+
+    cache_db_server.shutdown().unwrap();
+    assert!(!path.exists());
+}
+
+#[test]
+fn future_test() {
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 0);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let port = cache_db_server.local_addr().unwrap().port();
+
+    let client_config = CacheClientConfig{write_concern: WriteConcern::Acknowledged, ..Default::default()};
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect_with_config([127, 0, 0, 1], port, client_config).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+
+    // the requests are sent before the futures are awaited
+    let push_futures: Vec<_> = (0..100).map(|i| cache_client.push_async(KeyValObj{key: CacheString(format!("future_key{}", i)), val: CacheString(format!("val{}", i))})).collect();
+    for push_future in push_futures {
+        block_on(push_future).unwrap();
+    }
+    assert_eq!(cache.stats().entries, 100);
+
+    let pull_futures: Vec<_> = (0..100).map(|i| cache_client.pull_async(&CacheString(format!("future_key{}", i)))).collect();
+    for (i, pull_future) in pull_futures.into_iter().enumerate() {
+        assert_eq!(block_on(pull_future).unwrap().val.0, format!("val{}", i));
+    }
+
+    let delete_future = cache_client.delete_async(&CacheString("future_key0".to_string()));
+    let missing_delete_future = cache_client.delete_async(&CacheString("future_missing_key".to_string()));
+    assert_eq!(block_on(delete_future), Ok(true));
+    assert_eq!(block_on(missing_delete_future), Ok(false));
+
+    cache_db_server.shutdown().unwrap();
+    assert_eq!(block_on(cache_client.delete_async(&CacheString("future_key1".to_string()))), Err(CacheDbError::NetworkError));
+
+    // pending futures fail once the connection is lost
+    let cache_client = CacheClient::<CacheString, CacheString>::connect(closing_server()).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+    assert_eq!(block_on(cache_client.pull_async(&CacheString("future_key1".to_string()))).map(|res| res.val), Err(CacheDbError::NetworkError));
+}
+
+// accepts a single client, completes the hello exchange and closes the connection once the first request arrived
+fn closing_server() -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let (mut tcp_stream, _) = listener.accept().unwrap();
+        ProtHello::read_hello(&mut tcp_stream, ProtOpCode::HelloOp).unwrap();
+        let hello = ProtHello{version: CACHE_PROTOCOL_VERSION, capabilities: CACHE_PROTOCOL_CAP_TTL, identity: "closing-server".to_string()};
+        tcp_stream.write_all(&hello.assemble_buff(ProtOpCode::HelloReplyOp).unwrap()).unwrap();
+        let mut buff = [0; 1024];
+        let _ = tcp_stream.read(&mut buff);
+    });
+    server_addr
+}
+
+#[test]
+fn pull_table_test() {
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 0);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let port = cache_db_server.local_addr().unwrap().port();
+
+    let client_config = CacheClientConfig{write_concern: WriteConcern::Acknowledged, ..Default::default()};
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect_with_config([127, 0, 0, 1], port, client_config).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+    let key = CacheString("table_key".to_string());
+    let mut res = KeyValObj{key: key.clone(), val: CacheString::default()};
+
+    // a KeyNotFound does not stick to the key once it has been pushed
+    assert_eq!(cache_client.pull(&key, &mut res), Err(CacheDbError::KeyNotFound));
+    cache_client.push(KeyValObj{key: key.clone(), val: CacheString("val".to_string())}).unwrap();
+    cache_client.pull(&key, &mut res).unwrap();
+    assert_eq!(res.val.0, "val");
+
+    // the table only holds the pulls that are in flight, no matter how many keys have been pulled
+    let mut pull_threads = Vec::new();
+    for t in 0..4 {
+        let cache_client = Arc::clone(&cache_client);
+        pull_threads.push(thread::spawn(move || {
+            for i in 0..500 {
+                let key = CacheString(format!("table_key{}", (t * 500 + i) % 50));
+                let mut res = KeyValObj{key: key.clone(), val: CacheString::default()};
+                assert_eq!(cache_client.pull(&key, &mut res), Err(CacheDbError::KeyNotFound));
+                assert!(cache_client.pulls_in_flight() <= 4);
+            }
+        }));
+    }
+    for pull_thread in pull_threads {
+        pull_thread.join().unwrap();
+    }
+    assert_eq!(cache_client.pulls_in_flight(), 0);
+}
+
+#[test]
+fn near_cache_test() {
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 0);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let port = cache_db_server.local_addr().unwrap().port();
+
+    let client_config = CacheClientConfig{write_concern: WriteConcern::Acknowledged, near_cache: Some(NearCacheConfig{max_entries: 2, ttl: time::Duration::from_secs(60)}), ..Default::default()};
+    let near_client = CacheClient::<CacheString, CacheString>::create_connect_with_config([127, 0, 0, 1], port, client_config).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&near_client);
+    assert_ne!(near_client.server_hello().capabilities & CACHE_PROTOCOL_CAP_INVALIDATION, 0);
+    let client_config = CacheClientConfig{write_concern: WriteConcern::Acknowledged, ..Default::default()};
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect_with_config([127, 0, 0, 1], port, client_config).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+    assert_eq!(cache_client.near_cache_stats(), None);
+
+    let key = CacheString("near_key".to_string());
+    let mut res = KeyValObj{key: key.clone(), val: CacheString::default()};
+    cache_client.push(KeyValObj{key: key.clone(), val: CacheString("val".to_string())}).unwrap();
+    near_client.pull(&key, &mut res).unwrap();
+    near_client.pull(&key, &mut res).unwrap();
+    assert_eq!(res.val.0, "val");
+    assert_eq!(near_client.near_cache_stats().unwrap().hits, 1);
+
+    // waits until the InvalidateOp of a change arrived and returns the pulled val
+    let pull_changed = |prev_invalidations: u64| {
+        let deadline = time::Instant::now() + time::Duration::from_secs(2);
+        while near_client.near_cache_stats().unwrap().invalidations == prev_invalidations && time::Instant::now() < deadline {
+            thread::sleep(time::Duration::from_millis(5));
+        }
+        let mut res = KeyValObj{key: key.clone(), val: CacheString::default()};
+        near_client.pull(&key, &mut res).map(|_| res.val.0)
+    };
+
+    // changes by other clients and by the server itself are invalidated
+    cache_client.push(KeyValObj{key: key.clone(), val: CacheString("val1".to_string())}).unwrap();
+    assert_eq!(pull_changed(0), Ok("val1".to_string()));
+    cache.set(key.clone(), CacheString("val2".to_string())).unwrap();
+    assert_eq!(pull_changed(1), Ok("val2".to_string()));
+    assert!(cache_client.delete(&key).unwrap());
+    assert_eq!(pull_changed(2), Err(CacheDbError::KeyNotFound));
+
+    // the own writes of the client are read right away
+    near_client.push(KeyValObj{key: key.clone(), val: CacheString("val3".to_string())}).unwrap();
+    near_client.pull(&key, &mut res).unwrap();
+    assert_eq!(res.val.0, "val3");
+    near_client.push(KeyValObj{key: key.clone(), val: CacheString("val4".to_string())}).unwrap();
+    near_client.pull(&key, &mut res).unwrap();
+    assert_eq!(res.val.0, "val4");
+
+    // the near cache is bounded
+    for i in 0..4 {
+        let key = CacheString(format!("near_key{}", i));
+        cache_client.push(KeyValObj{key: key.clone(), val: CacheString("val".to_string())}).unwrap();
+        near_client.pull(&key, &mut res).unwrap();
+    }
+    assert_eq!(near_client.near_cache_stats().unwrap().entries, 2);
+}
+
+#[test]
+fn near_cache_eviction_test() {
+    let cache = CacheDb::<CacheString, CacheString>::new_with_config([127, 0, 0, 1], 0, CacheDbConfig{max_entries: Some(1), ..Default::default()});
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let client_config = CacheClientConfig{near_cache: Some(NearCacheConfig{max_entries: 2, ttl: time::Duration::from_secs(60)}), ..Default::default()};
+    let near_client = CacheClient::<CacheString, CacheString>::connect_with_config(cache_db_server.local_addr().unwrap(), client_config).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&near_client);
+
+    // waits until the InvalidateOp of a removed key arrived and pulls it again
+    let pull_removed = |key: &CacheString, prev_invalidations: u64| {
+        let deadline = time::Instant::now() + time::Duration::from_secs(3);
+        while near_client.near_cache_stats().unwrap().invalidations == prev_invalidations && time::Instant::now() < deadline {
+            thread::sleep(time::Duration::from_millis(5));
+        }
+        let mut res = KeyValObj{key: key.clone(), val: CacheString::default()};
+        near_client.pull(key, &mut res)
+    };
+
+    // evicted keys are invalidated
+    let key = CacheString("evicted_key".to_string());
+    let mut res = KeyValObj{key: key.clone(), val: CacheString::default()};
+    cache.push(KeyValObj{key: key.clone(), val: CacheString("val".to_string())});
+    near_client.pull(&key, &mut res).unwrap();
+    cache.push(KeyValObj{key: CacheString("other_key".to_string()), val: CacheString("val".to_string())});
+    assert_eq!(pull_removed(&key, 0), Err(CacheDbError::KeyNotFound));
+
+    // expired keys are invalidated once they have been swept
+    let key = CacheString("expired_key".to_string());
+    cache.push_with_ttl(KeyValObj{key: key.clone(), val: CacheString("val".to_string())}, time::Duration::from_millis(100));
+    near_client.pull(&key, &mut res).unwrap();
+    assert_eq!(pull_removed(&key, 1), Err(CacheDbError::KeyNotFound));
+    cache_db_server.shutdown().unwrap();
+}
+
+#[test]
+fn stalled_client_test() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_clone = Arc::clone(&events);
+    let config = CacheDbConfig{
+        server_event_hook: Some(Arc::new(move |event: &ServerEvent| events_clone.lock().unwrap().push(event.clone()))),
+        write_timeout: Some(time::Duration::from_millis(500)),
+        ..Default::default()
+    };
+    let cache = CacheDb::<CacheString, CacheString>::new_with_config([127, 0, 0, 1], 0, config);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let keys: Vec<CacheString> = (0..1000).map(|i| CacheString(format!("stalled_key{}", i))).collect();
+    for key in keys.iter() {
+        cache.push(KeyValObj{key: key.clone(), val: CacheString("x".repeat(60_000))});
+    }
+
+    // a client with a near cache that pulls far more than the socket buffers hold, but never reads the replies
+    let mut tcp_stream = TcpStream::connect(cache_db_server.local_addr().unwrap()).unwrap();
+    let client_addr = ConnAddr::Tcp(tcp_stream.local_addr().unwrap());
+    let hello = ProtHello{version: CACHE_PROTOCOL_VERSION, capabilities: CACHE_PROTOCOL_CAP_INVALIDATION, identity: "stalled-client".to_string()};
+    tcp_stream.write_all(&hello.assemble_buff(ProtOpCode::HelloOp).unwrap()).unwrap();
+    ProtHello::read_hello(&mut tcp_stream, ProtOpCode::HelloReplyOp).unwrap();
+    for (i, key) in keys.iter().enumerate() {
+        tcp_stream.write_all(&CacheProtocol::assemble_buff(FrameMode::Standard, ProtOpCode::PullOp, i as u32 + 1, &KeyValObj{key: key.clone(), val: CacheString::default()}, None).unwrap()).unwrap();
+    }
+    thread::sleep(time::Duration::from_millis(100));
+
+    // the invalidations for the client are queued, so the pushes do not wait for it
+    let start = time::Instant::now();
+    for key in keys.iter() {
+        cache.push(KeyValObj{key: key.clone(), val: CacheString("y".repeat(60_000))});
+    }
+    assert!(start.elapsed() < time::Duration::from_millis(400));
+
+    // the client is closed once the write timeout expired
+    let deadline = time::Instant::now() + time::Duration::from_secs(5);
+    while !events.lock().unwrap().iter().any(|event| matches!(event, ServerEvent::Closed(addr, ConnCloseReason::IoError(_)) if *addr == client_addr)) {
+        assert!(time::Instant::now() < deadline);
+        thread::sleep(time::Duration::from_millis(10));
+    }
+    cache_db_server.shutdown().unwrap();
+}
+
+#[test]
+fn watch_test() {
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 0);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let port = cache_db_server.local_addr().unwrap().port();
+
+    let client_config = CacheClientConfig{write_concern: WriteConcern::Acknowledged, ..Default::default()};
+    let watch_client = CacheClient::<CacheString, CacheString>::create_connect_with_config([127, 0, 0, 1], port, client_config.clone()).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&watch_client);
+    let cache_client = CacheClient::<CacheString, CacheString>::create_connect_with_config([127, 0, 0, 1], port, client_config).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+
+    let timeout = time::Duration::from_secs(2);
+    let key = CacheString("watch_key".to_string());
+    let key_events = watch_client.watch(WatchTarget::Key(key.clone())).unwrap();
+    let prefix_events = watch_client.watch(WatchTarget::Prefix(CacheString("user:".to_string()))).unwrap();
+
+    // pushes, sets and deletes of any connection are notified
+    cache_client.push(KeyValObj{key: key.clone(), val: CacheString("val".to_string())}).unwrap();
+    assert_eq!(key_events.recv_timeout(timeout), Ok(WatchEvent::Changed(key.clone(), CacheString("val".to_string()))));
+    cache.set(key.clone(), CacheString("val1".to_string())).unwrap();
+    assert_eq!(key_events.recv_timeout(timeout), Ok(WatchEvent::Changed(key.clone(), CacheString("val1".to_string()))));
+    assert!(cache_client.delete(&key).unwrap());
+    assert_eq!(key_events.recv_timeout(timeout), Ok(WatchEvent::Deleted(key.clone())));
+
+    let user_key = CacheString("user:1".to_string());
+    watch_client.push(KeyValObj{key: user_key.clone(), val: CacheString("brian".to_string())}).unwrap();
+    assert_eq!(prefix_events.recv_timeout(timeout), Ok(WatchEvent::Changed(user_key.clone(), CacheString("brian".to_string()))));
+    assert!(key_events.try_recv().is_err());
+
+    let callback_events = Arc::new(Mutex::new(Vec::new()));
+    let callback_events_clone = Arc::clone(&callback_events);
+    watch_client.watch_with_callback(WatchTarget::Key(CacheString("batch_key".to_string())), Arc::new(move |event: &WatchEvent<CacheString, CacheString>| callback_events_clone.lock().unwrap().push(event.clone()))).unwrap();
+    cache_client.push_many(&[KeyValObj{key: CacheString("batch_key".to_string()), val: CacheString("val".to_string())}, KeyValObj{key: user_key.clone(), val: CacheString("paul".to_string())}], None).unwrap();
+    assert_eq!(prefix_events.recv_timeout(timeout), Ok(WatchEvent::Changed(user_key.clone(), CacheString("paul".to_string()))));
+    let deadline = time::Instant::now() + timeout;
+    while callback_events.lock().unwrap().is_empty() && time::Instant::now() < deadline {
+        thread::sleep(time::Duration::from_millis(5));
+    }
+    assert_eq!(*callback_events.lock().unwrap(), vec![WatchEvent::Changed(CacheString("batch_key".to_string()), CacheString("val".to_string()))]);
+
+    // unwatched targets and lost connections disconnect the receivers
+    watch_client.unwatch(&WatchTarget::Key(key.clone())).unwrap();
+    assert_eq!(key_events.recv_timeout(timeout), Err(mpsc::RecvTimeoutError::Disconnected));
+    cache_db_server.shutdown().unwrap();
+    assert_eq!(prefix_events.recv_timeout(timeout), Err(mpsc::RecvTimeoutError::Disconnected));
+}
+
+#[test]
+fn concurrent_watch_test() {
+    let cache = CacheDb::<CacheString, CacheString>::new([127, 0, 0, 1], 0);
+    let cache_db_server = CacheDb::<CacheString, CacheString>::cache_db_server(&cache).unwrap();
+    let watch_client = CacheClient::<CacheString, CacheString>::connect(cache_db_server.local_addr().unwrap()).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&watch_client);
+    let key = CacheString("concurrent_key".to_string());
+    let events = watch_client.watch(WatchTarget::Key(key.clone())).unwrap();
+
+    let writers: Vec<_> = (0..4).map(|t| {
+        let cache = Arc::clone(&cache);
+        let key = key.clone();
+        thread::spawn(move || {
+            for i in 0..200 {
+                cache.push(KeyValObj{key: key.clone(), val: CacheString(format!("val{}_{}", t, i))});
+            }
+        })
+    }).collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    // the changes of a key are notified in the order they were stored, so the last event holds the stored val
+    let mut last_event = None;
+    while let Ok(event) = events.recv_timeout(time::Duration::from_millis(500)) {
+        last_event = Some(event);
+    }
+    assert_eq!(last_event, Some(WatchEvent::Changed(key.clone(), cache.get(&key).unwrap().val)));
+    cache_db_server.shutdown().unwrap();
+}
+
+// acknowledges the first watch of the client but not the second, then notifies a change of the watched key
+fn ack_once_server(key: CacheString) -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let (mut tcp_stream, _) = listener.accept().unwrap();
+        ProtHello::read_hello(&mut tcp_stream, ProtOpCode::HelloOp).unwrap();
+        let hello = ProtHello{version: CACHE_PROTOCOL_VERSION, capabilities: CACHE_PROTOCOL_CAP_TTL, identity: "ack-once-server".to_string()};
+        tcp_stream.write_all(&hello.assemble_buff(ProtOpCode::HelloReplyOp).unwrap()).unwrap();
+        let mut parser = CacheProtocol::<CacheString, CacheString>::new();
+        let mut parsed_op_code = ProtOpCode::PullOp;
+        let mut parsed_obj = KeyValObj{key: CacheString::default(), val: CacheString::default()};
+        let mut watches = 0;
+        let mut buff = [0; 1024];
+        while watches < 2 {
+            let size = tcp_stream.read(&mut buff).unwrap();
+            parser.feed(&buff[..size]);
+            while parser.parse_buff(&mut parsed_op_code, &mut parsed_obj).unwrap() {
+                watches += 1;
+                if watches == 1 {
+                    tcp_stream.write_all(&CacheProtocol::assemble_buff(FrameMode::Standard, ProtOpCode::WatchAckOp, parser.parsed_req_id(), &parsed_obj, None).unwrap()).unwrap();
+                }
+            }
+        }
+        // the second watch has timed out by now
+        thread::sleep(time::Duration::from_millis(300));
+        tcp_stream.write_all(&CacheProtocol::assemble_buff(FrameMode::Standard, ProtOpCode::ChangeNotifyOp, 0, &KeyValObj{key, val: CacheString("val".to_string())}, None).unwrap()).unwrap();
+        while tcp_stream.read(&mut buff).map(|size| size > 0).unwrap_or(false) {}
+    });
+    server_addr
+}
+
+#[test]
+fn failed_watch_test() {
+    let key = CacheString("watch_key".to_string());
+    let client_config = CacheClientConfig{request_timeout: time::Duration::from_millis(50), read_timeout: None, ..Default::default()};
+    let cache_client = CacheClient::<CacheString, CacheString>::connect_with_config(ack_once_server(key.clone()), client_config).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+
+    let acked_events = cache_client.watch(WatchTarget::Key(key.clone())).unwrap();
+    let failed_events = cache_client.watch(WatchTarget::Key(key.clone()));
+    assert_eq!(failed_events.err(), Some(CacheDbError::NetworkTimeOutError));
+    // the acknowledged watch of the same key is kept
+    assert_eq!(acked_events.recv_timeout(time::Duration::from_secs(2)), Ok(WatchEvent::Changed(key, CacheString("val".to_string()))));
+}
+
+// accepts a single client and completes the hello exchange, but never replies to a request
+fn silent_server() -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let (mut tcp_stream, _) = listener.accept().unwrap();
+        ProtHello::read_hello(&mut tcp_stream, ProtOpCode::HelloOp).unwrap();
+        let hello = ProtHello{version: CACHE_PROTOCOL_VERSION, capabilities: CACHE_PROTOCOL_CAP_TTL, identity: "silent-server".to_string()};
+        tcp_stream.write_all(&hello.assemble_buff(ProtOpCode::HelloReplyOp).unwrap()).unwrap();
+        let mut buff = [0; 1024];
+        while tcp_stream.read(&mut buff).map(|size| size > 0).unwrap_or(false) {}
+    });
+    server_addr
+}
+
+#[test]
+fn timeout_test() {
+    let client_config = CacheClientConfig{request_timeout: time::Duration::from_millis(50), read_timeout: None, ..Default::default()};
+    let cache_client = CacheClient::<CacheString, CacheString>::connect_with_config(silent_server(), client_config).unwrap();
+    let _s = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+    let key = CacheString("timeout_key".to_string());
+    let mut res = KeyValObj{key: key.clone(), val: CacheString::default()};
+
+    let start = time::Instant::now();
+    assert_eq!(cache_client.pull(&key, &mut res), Err(CacheDbError::NetworkTimeOutError));
+    // the timed out pull does not block the following pulls of the key
+    assert_eq!(cache_client.pull_with_deadline(&key, &mut res, time::Instant::now() + time::Duration::from_millis(10)), Err(CacheDbError::NetworkTimeOutError));
+    assert_eq!(cache_client.delete(&key), Err(CacheDbError::NetworkTimeOutError));
+    assert_eq!(cache_client.ttl_with_deadline(&key, time::Instant::now()), Err(CacheDbError::NetworkTimeOutError));
+    assert!(block_on_with_deadline(cache_client.delete_async(&key), time::Instant::now() + time::Duration::from_millis(10)).is_none());
+    assert!(start.elapsed() < time::Duration::from_secs(2));
+    assert_eq!(cache_client.pulls_in_flight(), 0);
+
+    // the connection is treated as lost once no reply arrived within the read timeout
+    let client_config = CacheClientConfig{read_timeout: Some(time::Duration::from_millis(50)), ..Default::default()};
+    let cache_client = CacheClient::<CacheString, CacheString>::connect_with_config(silent_server(), client_config).unwrap();
+    let client_handler = CacheClient::<CacheString, CacheString>::cache_client_handler(&cache_client);
+    assert_eq!(block_on(cache_client.pull_async(&key)).map(|res| res.val), Err(CacheDbError::NetworkTimeOutError));
+    assert_eq!(client_handler.join().unwrap(), Err(CacheDbError::NetworkTimeOutError));
 }